@@ -1,7 +1,7 @@
 use criterion::{ black_box, criterion_group, criterion_main, Criterion };
 use std::path::Path;
 use tokio::runtime::Runtime;
-use extract_metadata::{ process_safetensors_file, walk_directory };
+use dataset_tools::{ process_safetensors_file, walk_directory };
 
 fn bench_process_safetensors_file(c: &mut Criterion) {
     let rt = Runtime::new().unwrap();
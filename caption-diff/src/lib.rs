@@ -0,0 +1,379 @@
+// caption-diff\src\lib.rs
+
+// This program compares the captions of two copies of a dataset, such as before and after
+// re-tagging or re-running a converter, and reports which tags every caption gained and lost.
+//
+// Samples are matched by the path of their image relative to the dataset, without the
+// extension, or with `--match hash` by the MD5 hash of the image, which also pairs renamed
+// images. Captions without an image are matched by their own path in stem mode. Tags are
+// compared without escapes, keep tokens included, so moving a tag does not count as a change
+// but rewriting `long hair` as `long_hair` does. A change to the sentences is reported too.
+//
+// The report lists every changed caption, the captions found in only one of the datasets and
+// how often each tag was gained or lost overall, as text or as JSON with `--format json`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use anyhow::{ Context, Result };
+use clap::ValueEnum;
+use dataset_tools::{
+    file_md5,
+    read_file_content,
+    unescape_tag,
+    Caption,
+    CommonArgs,
+    Dataset,
+    SidecarKind,
+    WalkOptions,
+};
+use log::warn;
+use serde::Serialize;
+use std::{
+    collections::{ BTreeMap, HashMap, HashSet },
+    io::{ self, stdout, Write },
+    path::{ Path, PathBuf },
+};
+
+/// Compare the captions of two datasets and report the tags gained and lost
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The dataset before the change
+    pub old: PathBuf,
+
+    /// The dataset after the change
+    pub new: PathBuf,
+
+    /// How samples are matched across the two datasets
+    #[arg(long = "match", value_enum, value_name = "BY", default_value_t = MatchBy::Stem)]
+    pub match_by: MatchBy,
+
+    /// Output format of the report
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+/// How samples are matched across the two datasets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MatchBy {
+    /// The path of the image relative to the dataset, without the extension
+    Stem,
+    /// The MD5 hash of the image
+    Hash,
+}
+
+/// The output format of the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// A line per changed caption and tables of the gained and lost tags
+    Text,
+    /// A single JSON object
+    Json,
+}
+
+/// A caption and the file it was read from.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// The caption file relative to its dataset, with `/` separators.
+    pub file: String,
+    pub caption: Caption,
+}
+
+/// The changes between two versions of a caption.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CaptionDiff {
+    /// Tags only in the new caption, in its order.
+    pub added: Vec<String>,
+    /// Tags only in the old caption, in its order.
+    pub removed: Vec<String>,
+    pub sentences_changed: bool,
+}
+
+impl CaptionDiff {
+    /// Returns `true` if the captions have the same tags and sentences.
+    #[must_use = "Determines if anything changed and the result should be checked"]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && !self.sentences_changed
+    }
+}
+
+/// Compares the tags and sentences of two captions.
+#[must_use = "Compares the captions and the result should be used"]
+pub fn diff_captions(old: &Caption, new: &Caption) -> CaptionDiff {
+    let old_tags = tag_names(old);
+    let new_tags = tag_names(new);
+    let old_set: HashSet<&String> = old_tags.iter().collect();
+    let new_set: HashSet<&String> = new_tags.iter().collect();
+    CaptionDiff {
+        added: new_tags.iter().filter(|tag| !old_set.contains(tag)).cloned().collect(),
+        removed: old_tags.iter().filter(|tag| !new_set.contains(tag)).cloned().collect(),
+        sentences_changed: old.sentences != new.sentences,
+    }
+}
+
+/// Returns the keep tokens and tags of a caption without escapes or duplicates.
+fn tag_names(caption: &Caption) -> Vec<String> {
+    let mut seen = HashSet::new();
+    caption.keep_tokens
+        .iter()
+        .map(|token| unescape_tag(token))
+        .chain(caption.tag_names())
+        .filter(|tag| seen.insert(tag.clone()))
+        .collect()
+}
+
+/// The changes of a caption found in both datasets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileDiff {
+    /// The caption file in the new dataset.
+    pub file: String,
+    #[serde(flatten)]
+    pub diff: CaptionDiff,
+}
+
+/// A tag and the number of captions that gained or lost it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagChange {
+    pub tag: String,
+    pub count: u32,
+}
+
+/// The differences between the captions of two datasets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    /// Number of captions found in both datasets.
+    pub matched: u32,
+    /// The captions that changed, sorted by file.
+    pub changed: Vec<FileDiff>,
+    /// Caption files only found in the old dataset.
+    pub only_old: Vec<String>,
+    /// Caption files only found in the new dataset.
+    pub only_new: Vec<String>,
+    /// Tags added to captions, most often first.
+    pub gained: Vec<TagChange>,
+    /// Tags removed from captions, most often first.
+    pub lost: Vec<TagChange>,
+}
+
+/// Compares two datasets whose captions are keyed by how their samples are matched.
+#[must_use = "Compares the datasets and the result should be used"]
+pub fn compare(old: &BTreeMap<String, Entry>, new: &BTreeMap<String, Entry>) -> Report {
+    let mut matched = 0;
+    let mut changed = Vec::new();
+    let mut gained: HashMap<String, u32> = HashMap::new();
+    let mut lost: HashMap<String, u32> = HashMap::new();
+    for (key, new_entry) in new {
+        let Some(old_entry) = old.get(key) else {
+            continue;
+        };
+        matched += 1;
+        let diff = diff_captions(&old_entry.caption, &new_entry.caption);
+        for tag in &diff.added {
+            *gained.entry(tag.clone()).or_default() += 1;
+        }
+        for tag in &diff.removed {
+            *lost.entry(tag.clone()).or_default() += 1;
+        }
+        if !diff.is_empty() {
+            changed.push(FileDiff { file: new_entry.file.clone(), diff });
+        }
+    }
+    changed.sort_by(|a, b| a.file.cmp(&b.file));
+
+    let only_in = |entries: &BTreeMap<String, Entry>, other: &BTreeMap<String, Entry>| {
+        let mut files: Vec<String> = entries
+            .iter()
+            .filter(|(key, _)| !other.contains_key(*key))
+            .map(|(_, entry)| entry.file.clone())
+            .collect();
+        files.sort();
+        files
+    };
+
+    Report {
+        matched,
+        changed,
+        only_old: only_in(old, new),
+        only_new: only_in(new, old),
+        gained: most_frequent(gained),
+        lost: most_frequent(lost),
+    }
+}
+
+/// Sorts tag counts by count, ties by tag.
+fn most_frequent(counts: HashMap<String, u32>) -> Vec<TagChange> {
+    let mut sorted: Vec<TagChange> = counts
+        .into_iter()
+        .map(|(tag, count)| TagChange { tag, count })
+        .collect();
+    sorted.sort_unstable_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    sorted
+}
+
+/// Returns a path relative to the dataset, with `/` separators.
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+/// Reads the captions of a dataset, keyed by how their samples are matched, skipping the
+/// `--exclude` globs of `options`.
+///
+/// # Errors
+///
+/// Returns an error if the dataset cannot be scanned or a caption or image cannot be read.
+pub async fn load_captions(
+    root: &Path,
+    match_by: MatchBy,
+    options: &WalkOptions
+) -> Result<BTreeMap<String, Entry>> {
+    let dataset = Dataset::scan_with(root, options).await?;
+
+    let mut captions: Vec<(String, &Path)> = Vec::new();
+    for sample in &dataset {
+        let Some(caption) = sample.caption_path() else {
+            continue;
+        };
+        let key = match match_by {
+            MatchBy::Stem => relative(root, &sample.image.with_extension("")),
+            MatchBy::Hash => file_md5(&sample.image)
+                .await
+                .with_context(|| format!("Failed to hash {}", sample.image.display()))?,
+        };
+        captions.push((key, caption));
+    }
+    if match_by == MatchBy::Stem {
+        for orphan in dataset.orphans().iter().filter(|s| s.kind == SidecarKind::Caption) {
+            captions.push((relative(root, &orphan.path.with_extension("")), &orphan.path));
+        }
+    }
+
+    let mut entries: BTreeMap<String, Entry> = BTreeMap::new();
+    for (key, path) in captions {
+        let file = relative(root, path);
+        if let Some(existing) = entries.get(&key) {
+            warn!("{file} matches the same sample as {}, it is skipped", existing.file);
+            continue;
+        }
+        let caption = Caption::parse(
+            &read_file_content(path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?
+        );
+        entries.insert(key, Entry { file, caption });
+    }
+    Ok(entries)
+}
+
+/// Writes the report in the given format.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the output cannot be written.
+pub fn write_report(format: OutputFormat, report: &Report, mut out: impl Write) -> io::Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for file in &report.changed {
+                let mut changes: Vec<String> = file.diff.added
+                    .iter()
+                    .map(|tag| format!("+{tag}"))
+                    .chain(file.diff.removed.iter().map(|tag| format!("-{tag}")))
+                    .collect();
+                if file.diff.sentences_changed {
+                    changes.push("sentences changed".to_string());
+                }
+                writeln!(out, "{}: {}", file.file, changes.join(", "))?;
+            }
+            for file in &report.only_old {
+                writeln!(out, "Only in old: {file}")?;
+            }
+            for file in &report.only_new {
+                writeln!(out, "Only in new: {file}")?;
+            }
+            for (title, tags) in [("gained", &report.gained), ("lost", &report.lost)] {
+                if !tags.is_empty() {
+                    writeln!(out, "\nTags {title}:")?;
+                    for tag in tags {
+                        writeln!(out, "{:>8}  {}", tag.count, tag.tag)?;
+                    }
+                }
+            }
+            writeln!(
+                out,
+                "\n{} of {} matched captions changed, {} only in old, {} only in new.",
+                report.changed.len(),
+                report.matched,
+                report.only_old.len(),
+                report.only_new.len()
+            )?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, report)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if either dataset cannot be read or the report cannot be written.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let options = common.walk_options();
+    let old = load_captions(&args.old, args.match_by, &options).await?;
+    let new = load_captions(&args.new, args.match_by, &options).await?;
+    let report = compare(&old, &new);
+    write_report(args.format, &report, stdout().lock())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(captions: &[(&str, &str)]) -> BTreeMap<String, Entry> {
+        captions
+            .iter()
+            .map(|(key, caption)| {
+                let entry = Entry { file: format!("{key}.txt"), caption: Caption::parse(caption) };
+                ((*key).to_string(), entry)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_captions() {
+        let old = Caption::parse(r"feral ||| wolf \(character\), canine, solo, A wolf howls.");
+        let new = Caption::parse("solo, wolf (character), feral, snow, A wolf howls.");
+        let diff = diff_captions(&old, &new);
+        assert_eq!(diff.added, ["snow"]);
+        assert_eq!(diff.removed, ["canine"]);
+        assert!(!diff.sentences_changed);
+
+        let diff = diff_captions(&old, &Caption::parse("feral ||| long_hair, A fox sits."));
+        assert_eq!(diff.added, ["long_hair"]);
+        assert!(diff.sentences_changed);
+    }
+
+    #[test]
+    fn test_compare() {
+        let old = entries(&[("a", "solo, canine"), ("b", "duo, canine"), ("c", "solo"), ("d", "")]);
+        let new = entries(&[("a", "solo, wolf"), ("b", "duo, wolf"), ("c", "solo"), ("e", "y")]);
+        let report = compare(&old, &new);
+        assert_eq!(report.matched, 3);
+        assert_eq!(report.changed.len(), 2);
+        assert_eq!(report.changed[0].file, "a.txt");
+        assert_eq!(report.only_old, ["d.txt"]);
+        assert_eq!(report.only_new, ["e.txt"]);
+        assert_eq!(report.gained, [TagChange { tag: "wolf".into(), count: 2 }]);
+        assert_eq!(report.lost, [TagChange { tag: "canine".into(), count: 2 }]);
+
+        let mut out = Vec::new();
+        write_report(OutputFormat::Text, &report, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("a.txt: +wolf, -canine\n"));
+        assert!(text.ends_with("2 of 3 matched captions changed, 1 only in old, 1 only in new.\n"));
+    }
+}
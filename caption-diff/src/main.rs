@@ -0,0 +1,26 @@
+// caption-diff\src\main.rs
+
+// Standalone `caption-diff` binary, the same tool is available as `dataset-tools caption-diff`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use clap::Parser;
+use dataset_tools::CommonArgs;
+
+#[derive(Parser)]
+#[command(name = "caption-diff", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: caption_diff::Args,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    caption_diff::run(cli.args, &cli.common).await
+}
@@ -0,0 +1,304 @@
+// check\src\captions.rs
+
+// Caption linting for `check captions`.
+//
+// Every line of a caption is scanned for stray commas, consecutive spaces, backslashes that
+// escape nothing, superscripts and unbalanced parentheses. The tags of the parsed caption are
+// checked for duplicates, unescaped parentheses and a mix of underscores and spaces.
+//
+// `fix` applies the fixes that cannot change what a caption means: it drops duplicate and
+// empty tags, stray separators, stray backslashes and consecutive spaces. A line is only
+// rewritten through `Caption` if the result parses back into the same caption, otherwise only
+// the backslashes and spaces are fixed.
+
+use std::collections::HashSet;
+use dataset_tools::{ e621::e621_name, unescape_tag, Caption, KEEP_SEPARATOR };
+
+/// Superscript digits, which OCR and copy-pasted footnotes leave in captions.
+const SUPERSCRIPTS: &[char] = &['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+
+/// A problem found in a caption.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    /// The 1-based line, if the problem is on one.
+    pub line: Option<usize>,
+    /// The id of the rule, one of [`crate::report::RULES`].
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl Lint {
+    fn new(line: Option<usize>, rule: &'static str, message: impl Into<String>) -> Self {
+        Self { line, rule, message: message.into() }
+    }
+}
+
+/// Returns the problems found in a caption.
+#[must_use = "Lints the caption and the result should be used"]
+pub fn lint(content: &str) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        lint_line(line, i + 1, &mut lints);
+    }
+    lint_tags(content, &mut lints);
+    lints
+}
+
+/// Applies the safe fixes to a caption.
+#[must_use = "Returns the fixed caption and the result should be used"]
+pub fn fix(content: &str) -> String {
+    let mut fixed = content.lines().map(fix_line).collect::<Vec<_>>().join("\n");
+    if content.ends_with('\n') {
+        fixed.push('\n');
+    }
+    fixed
+}
+
+fn lint_line(line: &str, number: usize, lints: &mut Vec<Lint>) {
+    let line_number = Some(number);
+    let trimmed = line.trim();
+    if trimmed.starts_with(',') || (trimmed.ends_with(',') && !trimmed.ends_with("\\,")) {
+        lints.push(
+            Lint::new(line_number, "trailing-separator", "Line starts or ends with a comma")
+        );
+    }
+    if line.contains("  ") {
+        lints.push(Lint::new(line_number, "double-space", "Consecutive spaces"));
+    }
+    if let Some(c) = line.chars().find(|c| SUPERSCRIPTS.contains(c)) {
+        lints.push(Lint::new(line_number, "superscript", format!("Superscript `{c}`")));
+    }
+
+    let mut depth = 0usize;
+    let mut unbalanced = false;
+    let mut stray_backslash = false;
+    let mut empty_tag = false;
+    // Whether a separator was seen, and only whitespace since the last one
+    let mut after_separator = false;
+    let mut blank = true;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if !matches!(chars.next(), Some('(' | ')' | ',' | '\\')) {
+                    stray_backslash = true;
+                }
+                blank = false;
+            }
+            '(' => {
+                depth += 1;
+                blank = false;
+            }
+            ')' => {
+                match depth.checked_sub(1) {
+                    Some(d) => depth = d,
+                    None => unbalanced = true,
+                }
+                blank = false;
+            }
+            ',' if depth == 0 => {
+                if after_separator && blank {
+                    empty_tag = true;
+                }
+                after_separator = true;
+                blank = true;
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                blank = false;
+            }
+        }
+    }
+
+    if empty_tag {
+        lints.push(Lint::new(line_number, "empty-tag", "Empty tag between two commas"));
+    }
+    if unbalanced || depth > 0 {
+        lints.push(Lint::new(line_number, "unbalanced-parentheses", "Unbalanced parentheses"));
+    }
+    if stray_backslash {
+        lints.push(Lint::new(line_number, "stray-backslash", "Backslash that escapes nothing"));
+    }
+}
+
+fn lint_tags(content: &str, lints: &mut Vec<Lint>) {
+    let caption = Caption::parse(content);
+    let tags: Vec<&String> = caption.keep_tokens.iter().chain(&caption.tags).collect();
+
+    let mut seen = HashSet::new();
+    let mut tag_lines = TagLines { content, position: 0 };
+    for tag in &tags {
+        let line = tag_lines.next_line(tag);
+        if !seen.insert(e621_name(tag)) {
+            lints.push(Lint::new(line, "duplicate-tag", format!("Duplicate tag `{tag}`")));
+        }
+        if has_unescaped_parentheses(tag) {
+            let message = format!("Unescaped parentheses in `{tag}`");
+            lints.push(Lint::new(line, "unescaped-parentheses", message));
+        }
+    }
+
+    let underscores = tags.iter().any(|tag| is_underscore_style(tag));
+    let spaces = tags.iter().any(|tag| unescape_tag(tag).contains(' '));
+    if underscores && spaces {
+        let message = "Tags use both underscores and spaces between words";
+        lints.push(Lint::new(None, "mixed-tag-style", message));
+    }
+}
+
+/// Finds the lines of the tags of a caption, in caption order. Each tag is searched after the
+/// previous one and only as a whole tag between separators, so `cat` is not found in `catgirl`
+/// and a duplicate is found where it repeats.
+struct TagLines<'a> {
+    content: &'a str,
+    position: usize,
+}
+
+impl TagLines<'_> {
+    /// Returns the line of the next occurrence of a tag, or `None` if it is not written as
+    /// parsed, such as with collapsed whitespace.
+    fn next_line(&mut self, tag: &str) -> Option<usize> {
+        let mut from = self.position;
+        while let Some(i) = self.content[from..].find(tag).map(|i| i + from) {
+            let end = i + tag.len();
+            if is_tag_start(&self.content[..i]) && is_tag_end(&self.content[end..]) {
+                self.position = end;
+                return Some(self.content[..i].matches('\n').count() + 1);
+            }
+            from = i + tag.chars().next().map_or(1, char::len_utf8);
+        }
+        None
+    }
+}
+
+/// Returns `true` if a tag can start after this text.
+fn is_tag_start(before: &str) -> bool {
+    let before = before.trim_end_matches([' ', '\t']);
+    before.is_empty() || before.ends_with([',', '\n']) || before.ends_with(KEEP_SEPARATOR)
+}
+
+/// Returns `true` if a tag can end before this text, including the period of a legacy
+/// `tags., sentences` caption.
+fn is_tag_end(after: &str) -> bool {
+    let after = after.trim_start_matches([' ', '\t']);
+    after.is_empty() ||
+        after.starts_with([',', '\n']) ||
+        after.starts_with(KEEP_SEPARATOR) ||
+        after.strip_prefix('.').is_some_and(|rest| rest.trim_start().starts_with(','))
+}
+
+fn has_unescaped_parentheses(tag: &str) -> bool {
+    let mut escaped = false;
+    for c in tag.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '(' || c == ')' {
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns `true` if a tag joins words with underscores, unlike emoticons such as `^_^`.
+fn is_underscore_style(tag: &str) -> bool {
+    let chars: Vec<char> = tag.chars().collect();
+    chars.windows(3).any(|w| w[0].is_alphanumeric() && w[1] == '_' && w[2].is_alphanumeric())
+}
+
+fn fix_line(line: &str) -> String {
+    let line = remove_stray_backslashes(line);
+    let line = collapse_spaces(&line);
+    if line.trim().is_empty() {
+        return line;
+    }
+
+    let mut caption = Caption::parse(&line);
+    let mut seen: HashSet<String> = caption.keep_tokens
+        .iter()
+        .map(|tag| e621_name(tag))
+        .collect();
+    caption.tags.retain(|tag| seen.insert(e621_name(tag)));
+    caption.normalize();
+
+    // Only rewrite captions that survive the round trip, such as not the legacy `tags., text`
+    let rewritten = caption.to_string();
+    if Caption::parse(&rewritten) == caption { rewritten } else { line }
+}
+
+fn remove_stray_backslashes(line: &str) -> String {
+    let mut fixed = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            // Valid escapes are kept whole, anything else loses the backslash
+            if let Some(&next @ ('(' | ')' | ',' | '\\')) = chars.peek() {
+                fixed.push(c);
+                fixed.push(next);
+                chars.next();
+            }
+        } else {
+            fixed.push(c);
+        }
+    }
+    fixed
+}
+
+fn collapse_spaces(line: &str) -> String {
+    let mut fixed = String::with_capacity(line.len());
+    for c in line.chars() {
+        if !(c == ' ' && fixed.ends_with(' ')) {
+            fixed.push(c);
+        }
+    }
+    fixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(content: &str) -> Vec<&'static str> {
+        lint(content).into_iter().map(|lint| lint.rule).collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        assert!(rules(r"solo, wolf \(character\), A wolf (left) runs.").is_empty());
+        assert_eq!(rules("solo, wolf,"), ["trailing-separator"]);
+        assert_eq!(rules("solo,, wolf"), ["empty-tag"]);
+        assert_eq!(rules("solo,  wolf"), ["double-space"]);
+        assert_eq!(rules("solo, wolf²"), ["superscript"]);
+        assert_eq!(rules(r"solo, \wolf"), ["stray-backslash"]);
+        assert_eq!(rules("solo, wolf, Solo"), ["duplicate-tag"]);
+        assert_eq!(rules("solo, wolf (character)"), ["unescaped-parentheses"]);
+        assert_eq!(rules("long_hair, short hair, ^_^"), ["mixed-tag-style"]);
+        assert_eq!(
+            rules("solo, wolf \\(character"),
+            Vec::<&str>::new(),
+            "escaped parentheses do not need to balance"
+        );
+        assert_eq!(rules("solo, (wolf"), ["unbalanced-parentheses", "unescaped-parentheses"]);
+
+        let lints = lint("solo\nwolf, solo,");
+        assert_eq!(lints[0].line, Some(2));
+
+        // Tags are found whole, and a duplicate where it repeats
+        let lints = lint("catgirl, solo\ncat, cat, solo");
+        let found: Vec<_> = lints.iter().map(|lint| (lint.rule, lint.line)).collect();
+        assert_eq!(found, [("duplicate-tag", Some(2)), ("duplicate-tag", Some(2))]);
+        let lints = lint("catgirl, cat (ears)");
+        assert_eq!((lints[0].rule, lints[0].line), ("unescaped-parentheses", Some(1)));
+    }
+
+    #[test]
+    fn test_fix() {
+        assert_eq!(fix("solo,, wolf,  \\w, solo, A  wolf.\n"), "solo, wolf, w, A wolf.\n");
+        assert_eq!(fix("feral ||| feral, solo"), "feral ||| solo");
+        // The legacy form would lose its separator, so only spaces are fixed
+        assert_eq!(fix("cat,  dog., a cat"), "cat, dog., a cat");
+        let unsafe_problems = "long_hair, short hair, wolf (character)";
+        assert_eq!(fix(unsafe_problems), unsafe_problems);
+    }
+}
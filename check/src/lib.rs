@@ -0,0 +1,661 @@
+// check\src\lib.rs
+
+// This program is used to check for different things, it supports looking for rust
+// attributes and multiple lines in text files.
+//
+// `check captions` lints the content of captions, reporting duplicate and empty tags, stray
+// commas and backslashes, unbalanced or unescaped parentheses, consecutive spaces,
+// superscripts and tags mixing underscores and spaces. With `--fix` the safe fixes are
+// applied and only the remaining findings are reported. With `--fix --dry-run`, the fixes are
+// only printed and the findings describe the captions as they are on disk.
+//
+// With `--format json|ndjson|sarif` the findings are printed in a machine-readable format
+// instead. The exit code is 0 when nothing was found, 1 when there are findings and 2 when
+// the check itself failed.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use clap::Subcommand;
+use dataset_tools::{
+    journal,
+    walk_rust_files_with,
+    read_lines,
+    walk_directory_with,
+    check_file_for_multiple_lines,
+    open_files_in_neovim,
+    read_file_content,
+    process_rust_file,
+    write_to_file,
+    CommonArgs,
+    Dataset,
+    FileSelector,
+    WalkOptions,
+    WalkSummary,
+};
+use regex::Regex;
+use crossterm::{ style::{ Color, SetForegroundColor, ResetColor, Stylize }, ExecutableCommand };
+use std::{ io, io::stdout, path::{ PathBuf, Path }, process::ExitCode, sync::Arc };
+use tokio::sync::Mutex;
+use anyhow::{ bail, Result, Context };
+use toml::Value;
+
+pub mod captions;
+pub mod report;
+
+use report::{ write_findings, Finding, OutputFormat };
+
+/// Check Rust sources and datasets for common problems
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Commands,
+
+    /// How to print the findings
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Find built-in attributes in Rust files
+    Attributes {
+        directory: Option<PathBuf>,
+    },
+    /// Find captions spanning multiple lines and open them in Neovim
+    Multiline {
+        directory: Option<PathBuf>,
+    },
+    /// Find Cargo.toml files without the optimized build profiles
+    Optimizations {
+        directory: Option<PathBuf>,
+    },
+    /// Find Rust files without the pedantic clippy warning
+    Pedantic {
+        directory: Option<PathBuf>,
+    },
+    /// Find images without a caption or with an empty one
+    EmptyCaptions {
+        directory: Option<PathBuf>,
+    },
+    /// Find problems in the content of captions
+    Captions {
+        directory: Option<PathBuf>,
+
+        /// Apply the fixes that cannot change what a caption means
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+// List of built-in attributes in Rust
+#[rustfmt::skip]
+const ATTRIBUTES: &[&str] = &[
+    "cfg", "cfg_attr", "test", "ignore", "should_panic", //"derive",
+    "automatically_derived", "macro_export", "macro_use", "proc_macro",
+    "proc_macro_derive", "proc_macro_attribute", "allow", "warn",
+    "deny", "forbid", "deprecated", //"must_use",
+    "diagnostic::on_unimplemented", "link", "link_name", "link_ordinal",
+    "no_link", "repr", "crate_type", "no_main", "export_name", "link_section",
+    "no_mangle", "used", "crate_name", "inline", "cold", "no_builtins",
+    "target_feature", "track_caller", "instruction_set", "doc", "no_std",
+    "no_implicit_prelude", "path", "recursion_limit", "type_length_limit",
+    "panic_handler", "global_allocator", "windows_subsystem",
+	 "feature", "non_exhaustive", "debugger_visualizer", // "tokio::main",
+];
+
+/// Runs the check and maps its outcome to the exit code: 0 when nothing was found, 1 when
+/// there are findings and 2 when the check itself failed.
+pub async fn run_with_exit_code(args: Args, common: &CommonArgs) -> ExitCode {
+    match run(args, common).await {
+        Ok(findings) if findings.is_empty() => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Runs the check and prints its findings.
+///
+/// # Errors
+///
+/// Returns an error if the target is invalid or cannot be walked.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<Vec<Finding>> {
+    common.init()?;
+    let walk_options = common.walk_options();
+    let format = args.format;
+
+    let mut findings = match args.command {
+        Commands::Attributes { directory } => {
+            check_attributes(&common.dir_or(directory), &walk_options, format).await?
+        }
+        Commands::Multiline { directory } => {
+            let directory = common.dir_or(directory);
+            let captions = common.config(&directory)?.caption_extension;
+            check_multiline(&directory, &captions, &walk_options, format).await?
+        }
+        Commands::Optimizations { directory } => {
+            check_optimizations(&common.dir_or(directory), &walk_options, format).await?
+        }
+        Commands::Pedantic { directory } => {
+            check_pedantic(&common.dir_or(directory), &walk_options, format).await?
+        }
+        Commands::EmptyCaptions { directory } => {
+            let directory = common.dir_or(directory);
+            let captions = common.config(&directory)?.caption_extension;
+            check_empty_captions(&directory, &captions, &walk_options, format).await?
+        }
+        Commands::Captions { directory, fix } => {
+            let directory = common.dir_or(directory);
+            let captions = common.config(&directory)?.caption_extension;
+            check_captions(&directory, &captions, fix, &walk_options, format).await?
+        }
+    };
+
+    // Files are checked concurrently, so sort for a stable output
+    findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    write_findings(format, &findings, stdout().lock()).context("Failed to write findings")?;
+    Ok(findings)
+}
+
+/// Prints a list of files with a heading in text mode, or the message for no findings.
+fn print_files(format: OutputFormat, findings: &[Finding], heading: &str, none_found: &str) {
+    if format != OutputFormat::Text {
+        return;
+    }
+    if findings.is_empty() {
+        println!("{none_found}");
+    } else {
+        println!("{heading}");
+        for finding in findings {
+            println!("{}", finding.file.display());
+        }
+    }
+}
+
+/// Reports the walk in text mode. In machine-readable modes stdout is reserved for the
+/// findings, so only failures are listed, on stderr.
+fn finish_walk(summary: &WalkSummary, format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Text {
+        return summary.report();
+    }
+    for (path, e) in &summary.failed {
+        eprintln!("Failed: {}: {e:#}", path.display());
+    }
+    if !summary.is_success() {
+        bail!("{} files failed", summary.failed.len());
+    }
+    Ok(())
+}
+
+async fn check_pedantic(
+    target: &Path,
+    walk_options: &WalkOptions,
+    format: OutputFormat
+) -> Result<Vec<Finding>> {
+    let files_without_warning = Arc::new(Mutex::new(Vec::new()));
+
+    let canonical_target = target.canonicalize().context("Failed to canonicalize path")?;
+
+    if canonical_target.is_file() && canonical_target.extension().is_some_and(|ext| ext == "rs") {
+        let files_without_warning_clone = Arc::clone(&files_without_warning);
+        let mut guard = files_without_warning_clone.lock().await;
+        process_rust_file(&canonical_target, &mut guard).await?;
+    } else if canonical_target.is_dir() {
+        walk_rust_files_with(&canonical_target, walk_options, |path| {
+            let files_without_warning_clone = Arc::clone(&files_without_warning);
+            async move {
+                let mut guard = files_without_warning_clone.lock().await;
+                process_rust_file(&path, &mut guard).await
+            }
+        }).await.context("Failed to walk through Rust files")?;
+    } else {
+        bail!("Invalid target. Please provide a .rs file or a directory.");
+    }
+
+    let findings: Vec<_> = files_without_warning
+        .lock().await
+        .iter()
+        .map(|file| {
+            Finding::file(
+                file.clone(),
+                "pedantic",
+                "Missing `#![warn(clippy::all, clippy::pedantic)]`"
+            )
+        })
+        .collect();
+    print_files(
+        format,
+        &findings,
+        "The following files are missing the required warning:",
+        "All Rust files contain the required warning."
+    );
+
+    Ok(findings)
+}
+
+async fn check_optimizations(
+    target_path: &Path,
+    walk_options: &WalkOptions,
+    format: OutputFormat
+) -> Result<Vec<Finding>> {
+    let missing_configs = Arc::new(Mutex::new(Vec::new()));
+
+    if target_path.is_file() && target_path.file_name().unwrap() == "Cargo.toml" {
+        if !check_cargo_toml(target_path).await.unwrap_or(false) {
+            missing_configs.lock().await.push(target_path.to_owned());
+        }
+    } else if target_path.is_dir() {
+        let cargo_tomls = FileSelector::predicate(|path| {
+            path.file_name().is_some_and(|name| name == "Cargo.toml")
+        });
+        let summary = walk_directory_with(target_path, cargo_tomls, walk_options, |path: PathBuf| {
+            let missing_configs = Arc::clone(&missing_configs);
+            async move {
+                if !check_cargo_toml(&path).await.unwrap_or(false) {
+                    missing_configs.lock().await.push(path);
+                }
+                Ok(())
+            }
+        }).await?;
+        finish_walk(&summary, format)?;
+    } else {
+        bail!("Invalid path: {}", target_path.display());
+    }
+
+    let findings: Vec<_> = missing_configs
+        .lock().await
+        .iter()
+        .map(|file| {
+            Finding::file(file.clone(), "optimizations", "Missing the required profile settings")
+        })
+        .collect();
+    print_files(
+        format,
+        &findings,
+        "The following Cargo.toml files are missing the required configurations:",
+        "All Cargo.toml files contain the required configurations."
+    );
+
+    Ok(findings)
+}
+
+async fn check_cargo_toml(path: &Path) -> Result<bool> {
+    let content = read_file_content(path).await.context("Failed to read file")?;
+    let toml_value: Value = content.parse().context("Failed to parse TOML")?;
+
+    let Some(profile) = toml_value.get("profile") else {
+        return Ok(false);
+    };
+
+    // Check [profile.dev]
+    let Some(dev) = profile.get("dev") else {
+        return Ok(false);
+    };
+    if dev.get("opt-level") != Some(&Value::Integer(3)) {
+        return Ok(false);
+    }
+
+    // Check [profile.dev.package."*"]
+    let Some(dev_package) = dev.get("package").and_then(|p| p.get("*")) else {
+        return Ok(false);
+    };
+    if
+        dev_package.get("opt-level") != Some(&Value::Integer(3)) ||
+        dev_package.get("codegen-units") != Some(&Value::Integer(1))
+    {
+        return Ok(false);
+    }
+
+    // Check [profile.release]
+    let Some(release) = profile.get("release") else {
+        return Ok(false);
+    };
+    if
+        release.get("opt-level") != Some(&Value::Integer(3)) ||
+        release.get("lto") != Some(&Value::Boolean(true)) ||
+        release.get("codegen-units") != Some(&Value::Integer(1)) ||
+        release.get("strip") != Some(&Value::Boolean(true))
+    {
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+async fn check_attributes(
+    directory: &Path,
+    walk_options: &WalkOptions,
+    format: OutputFormat
+) -> Result<Vec<Finding>> {
+    let re = Arc::new(
+        Regex::new(
+            &format!(r"#\[\s*({})|#!\[\s*({})\]", ATTRIBUTES.join("|"), ATTRIBUTES.join("|"))
+        ).context("Failed to create regex")?
+    );
+
+    let found_attributes = Arc::new(Mutex::new(Vec::new()));
+    let found_attributes_clone = Arc::clone(&found_attributes);
+
+    walk_rust_files_with(directory, walk_options, move |path: PathBuf| {
+        let re = Arc::clone(&re);
+        let found_attributes = Arc::clone(&found_attributes_clone);
+        async move {
+            let lines = read_lines(&path).await?;
+            for (line_number, line) in lines.iter().enumerate() {
+                if re.is_match(line) {
+                    found_attributes.lock().await.push(Finding {
+                        file: path.clone(),
+                        line: Some(line_number + 1),
+                        rule: "attribute",
+                        message: line.trim().to_string(),
+                    });
+
+                    if format != OutputFormat::Text {
+                        continue;
+                    }
+                    stdout()
+                        .execute(SetForegroundColor(Color::Magenta))
+                        .map_err(io::Error::other)?;
+                    println!("{}:{}", path.display(), line_number + 1);
+                    stdout()
+                        .execute(ResetColor)
+                        .map_err(io::Error::other)?;
+
+                    let start = line_number.saturating_sub(3);
+                    let end = (line_number + 2).min(lines.len());
+                    for (i, line) in lines[start..end].iter().enumerate() {
+                        if i + start == line_number {
+                            let highlighted = re.replace_all(line, |caps: &regex::Captures| {
+                                format!("{}", caps[0].red())
+                            });
+                            println!("{highlighted}");
+                        } else {
+                            println!("{line}");
+                        }
+                    }
+                    println!();
+                }
+            }
+            Ok(())
+        }
+    }).await.context("Failed to walk rust files")?;
+
+    let findings = found_attributes.lock().await.clone();
+    Ok(findings)
+}
+
+async fn check_multiline(
+    directory: &Path,
+    caption_extension: &str,
+    walk_options: &WalkOptions,
+    format: OutputFormat
+) -> Result<Vec<Finding>> {
+    let multi_line_files = Arc::new(Mutex::new(Vec::new()));
+
+    let summary = walk_directory_with(directory, caption_extension, walk_options, |path| {
+        let multi_line_files = Arc::clone(&multi_line_files);
+        async move {
+            if format == OutputFormat::Text {
+                return check_file_for_multiple_lines(path, multi_line_files).await;
+            }
+            // Same check, without printing to stdout
+            let content = read_file_content(&path).await?;
+            if content.lines().count() > 1 {
+                multi_line_files.lock().await.push(path);
+            }
+            Ok(())
+        }
+    }).await.context("Failed to walk directory")?;
+
+    let files = multi_line_files.lock().await;
+    if format == OutputFormat::Text {
+        if files.is_empty() {
+            println!("No files with multiple lines found.");
+        } else {
+            println!("\nOpening files with multiple lines in Neovim...");
+            open_files_in_neovim(&files).await.context("Failed to open files in Neovim")?;
+        }
+    }
+
+    finish_walk(&summary, format)?;
+
+    Ok(
+        files
+            .iter()
+            .map(|file| Finding::file(file.clone(), "multiline", "Caption has multiple lines"))
+            .collect()
+    )
+}
+
+async fn check_empty_captions(
+    directory: &Path,
+    caption_extension: &str,
+    walk_options: &WalkOptions,
+    format: OutputFormat
+) -> Result<Vec<Finding>> {
+    let dataset = Dataset::scan_with(directory, walk_options).await?;
+
+    let mut summary = WalkSummary::default();
+    let mut findings = Vec::new();
+    for sample in &dataset {
+        let message = match sample.caption_with_extension(caption_extension) {
+            None => "Caption is missing",
+            Some(path) => match read_file_content(path).await {
+                Ok(content) if content.trim().is_empty() => "Caption is empty",
+                Ok(_) => {
+                    summary.processed += 1;
+                    continue;
+                }
+                Err(e) => {
+                    summary.failed.push((path.to_path_buf(), e.into()));
+                    continue;
+                }
+            },
+        };
+        summary.processed += 1;
+        findings.push(Finding::file(sample.image.clone(), "empty-caption", message));
+    }
+    print_files(
+        format,
+        &findings,
+        "The following image files have empty or missing captions:",
+        "No image files with empty or missing captions found."
+    );
+
+    finish_walk(&summary, format)?;
+
+    Ok(findings)
+}
+
+async fn check_captions(
+    directory: &Path,
+    caption_extension: &str,
+    fix: bool,
+    walk_options: &WalkOptions,
+    format: OutputFormat
+) -> Result<Vec<Finding>> {
+    let found = Arc::new(Mutex::new(Vec::new()));
+
+    let summary = walk_directory_with(directory, caption_extension, walk_options, |path| {
+        let found = Arc::clone(&found);
+        async move {
+            let mut content = read_file_content(&path).await?;
+            if fix {
+                let fixed = captions::fix(&content);
+                if fixed != content {
+                    let dry_run = journal::is_dry_run();
+                    if format == OutputFormat::Text {
+                        let verb = if dry_run { "Would fix" } else { "Fixed" };
+                        println!("{verb}: {}", path.display());
+                    }
+                    write_to_file(&path, &fixed).await?;
+                    // Nothing is written in a dry run, so the file on disk is what gets linted
+                    if !dry_run {
+                        content = fixed;
+                    }
+                }
+            }
+
+            let findings = captions::lint(&content)
+                .into_iter()
+                .map(|lint| Finding {
+                    file: path.clone(),
+                    line: lint.line,
+                    rule: lint.rule,
+                    message: lint.message,
+                });
+            found.lock().await.extend(findings);
+            Ok(())
+        }
+    }).await.context("Failed to walk directory")?;
+
+    let mut findings = found.lock().await.clone();
+    if format == OutputFormat::Text {
+        findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        if findings.is_empty() {
+            println!("No problems found in captions.");
+        }
+        for finding in &findings {
+            let location = match finding.line {
+                Some(line) => format!("{}:{line}", finding.file.display()),
+                None => finding.file.display().to_string(),
+            };
+            println!("{location}: {} {}", finding.rule.yellow(), finding.message);
+        }
+    }
+
+    finish_walk(&summary, format)?;
+
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_file(dir: &Path, name: &str, content: &str) -> Result<PathBuf> {
+        let path = dir.join(name);
+        fs::create_dir_all(dir)?;
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    #[tokio::test]
+    async fn test_check_pedantic() {
+        let temp_dir = TempDir::new().unwrap();
+        
+        // Create test files
+        let file_with_warning = create_test_file(
+            temp_dir.path(),
+            "with_warning.rs",
+            "#![warn(clippy::all, clippy::pedantic)]\nfn main() {}"
+        ).unwrap();
+
+        let file_without_warning = create_test_file(
+            temp_dir.path(),
+            "without_warning.rs",
+            "fn main() {}"
+        ).unwrap();
+
+        // Test directory with mixed files
+        let result = check_pedantic(temp_dir.path(), &WalkOptions::default(), OutputFormat::Json).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].file, file_without_warning);
+
+        // Test single file with warning
+        let result = check_pedantic(&file_with_warning, &WalkOptions::default(), OutputFormat::Json).await.unwrap();
+        assert!(result.is_empty());
+
+        // Test single file without warning
+        let result = check_pedantic(&file_without_warning, &WalkOptions::default(), OutputFormat::Json).await.unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_optimizations() {
+        let temp_dir = TempDir::new().unwrap();
+        
+        // Create test Cargo.toml files
+        create_test_file(
+            temp_dir.path(),
+            "Cargo.toml",
+            r#"
+[profile.dev]
+opt-level = 3
+
+[profile.dev.package."*"]
+opt-level = 3
+codegen-units = 1
+
+[profile.release]
+opt-level = 3
+lto = true
+codegen-units = 1
+strip = true
+            "#
+        ).unwrap();
+
+        create_test_file(
+            &temp_dir.path().join("subdir"),
+            "Cargo.toml",
+            "[package]\nname = \"test\"\nversion = \"0.1.0\""
+        ).unwrap();
+
+        // Test directory with both files
+        let result = check_optimizations(temp_dir.path(), &WalkOptions::default(), OutputFormat::Json).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(result[0].file.ends_with("subdir/Cargo.toml"));
+        assert_eq!(result[0].rule, "optimizations");
+    }
+
+    #[tokio::test]
+    async fn test_check_attributes() {
+        let temp_dir = TempDir::new().unwrap();
+        
+        create_test_file(
+            temp_dir.path(),
+            "with_attrs.rs",
+            r"
+#[derive(Debug)]
+#[cfg(test)]
+struct Test {}
+            "
+        ).unwrap();
+
+        let result = check_attributes(temp_dir.path(), &WalkOptions::default(), OutputFormat::Json).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].line, Some(3));
+        assert_eq!(result[0].message, "#[cfg(test)]");
+    }
+
+    #[tokio::test]
+    async fn test_check_empty_captions() {
+        let temp_dir = TempDir::new().unwrap();
+        for (name, content) in [
+            ("captioned.png", ""),
+            ("captioned.txt", "solo, wolf"),
+            ("empty.png", ""),
+            ("empty.txt", " \n"),
+            ("missing.png", ""),
+            ("sidecar.png", ""),
+            ("sidecar.png.txt", "solo"),
+        ] {
+            create_test_file(temp_dir.path(), name, content).unwrap();
+        }
+
+        let options = WalkOptions::default();
+        let result = check_empty_captions(temp_dir.path(), "txt", &options, OutputFormat::Json)
+            .await
+            .unwrap();
+        let found: Vec<_> = result
+            .iter()
+            .map(|finding| (finding.file.file_name().unwrap().to_str().unwrap(), &*finding.message))
+            .collect();
+        assert_eq!(found, [("empty.png", "Caption is empty"), ("missing.png", "Caption is missing")]);
+    }
+}
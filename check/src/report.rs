@@ -0,0 +1,173 @@
+// check\src\report.rs
+
+// Machine-readable output for the check subcommands.
+//
+// Every check produces a list of `Finding`s with the same schema: the file, the line if the
+// finding points at one, the id of the rule and a message. They can be printed as a JSON
+// array, as one JSON object per line, or as a SARIF 2.1.0 log for code scanning tools.
+
+use std::{ io::{ self, Write }, path::PathBuf };
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+
+/// The output format of the check subcommands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Coloured text for humans
+    Text,
+    /// A JSON array of findings
+    Json,
+    /// One JSON finding per line
+    Ndjson,
+    /// A SARIF 2.1.0 log
+    Sarif,
+}
+
+/// Every rule the checks report, with its description.
+pub const RULES: &[(&str, &str)] = &[
+    ("attribute", "Rust built-in attribute"),
+    ("multiline", "Caption file spans multiple lines"),
+    ("optimizations", "Cargo.toml is missing the required profile optimizations"),
+    ("pedantic", "Rust file is missing the clippy pedantic warning"),
+    ("empty-caption", "Image has an empty or missing caption"),
+    ("duplicate-tag", "Caption has the same tag more than once"),
+    ("empty-tag", "Caption has an empty tag between two commas"),
+    ("trailing-separator", "Caption line starts or ends with a comma"),
+    ("unbalanced-parentheses", "Caption line has unbalanced parentheses"),
+    ("unescaped-parentheses", "Tag has parentheses that are not escaped"),
+    ("double-space", "Caption has consecutive spaces"),
+    ("stray-backslash", "Caption has a backslash that escapes nothing"),
+    ("superscript", "Caption has superscript characters"),
+    ("mixed-tag-style", "Caption mixes tags with underscores and tags with spaces"),
+];
+
+/// A single problem found by a check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    /// The file the finding is about.
+    pub file: PathBuf,
+    /// The 1-based line of the finding, if it points at one.
+    pub line: Option<usize>,
+    /// The id of the rule, one of [`RULES`].
+    pub rule: &'static str,
+    /// A human-readable description of the problem.
+    pub message: String,
+}
+
+impl Finding {
+    /// Creates a finding about a whole file.
+    pub fn file(file: PathBuf, rule: &'static str, message: impl Into<String>) -> Self {
+        Self { file, line: None, rule, message: message.into() }
+    }
+}
+
+fn sarif(findings: &[Finding]) -> serde_json::Value {
+    let rules: Vec<_> = RULES.iter()
+        .map(|(id, description)| json!({ "id": id, "shortDescription": { "text": description } }))
+        .collect();
+    let results: Vec<_> = findings
+        .iter()
+        .map(|finding| {
+            let mut location = json!({
+                "artifactLocation": { "uri": finding.file.to_string_lossy().replace('\\', "/") },
+            });
+            if let Some(line) = finding.line {
+                location["region"] = json!({ "startLine": line });
+            }
+            json!({
+                "ruleId": finding.rule,
+                "level": "warning",
+                "message": { "text": finding.message },
+                "locations": [{ "physicalLocation": location }],
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "check",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// Writes the findings in a machine-readable format. Text output is printed by the checks
+/// themselves, so nothing is written for [`OutputFormat::Text`].
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the output cannot be written.
+pub fn write_findings(
+    format: OutputFormat,
+    findings: &[Finding],
+    mut out: impl Write
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, findings)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => {
+            for finding in findings {
+                serde_json::to_writer(&mut out, finding)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Sarif => {
+            serde_json::to_writer_pretty(&mut out, &sarif(findings))?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn findings() -> Vec<Finding> {
+        vec![
+            Finding {
+                file: PathBuf::from("src/main.rs"),
+                line: Some(3),
+                rule: "attribute",
+                message: "#[cfg(test)]".to_string(),
+            },
+            Finding::file(PathBuf::from("a.png"), "empty-caption", "Empty caption"),
+        ]
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let mut out = Vec::new();
+        write_findings(OutputFormat::Ndjson, &findings(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r##"{"file":"src/main.rs","line":3,"rule":"attribute","message":"#[cfg(test)]"}"##,
+                r#"{"file":"a.png","line":null,"rule":"empty-caption","message":"Empty caption"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sarif() {
+        let log = sarif(&findings());
+        let results = &log["runs"][0]["results"];
+        assert_eq!(results[0]["ruleId"], "attribute");
+        assert_eq!(results[0]["locations"][0]["physicalLocation"]["region"]["startLine"], 3);
+        assert!(results[1]["locations"][0]["physicalLocation"].get("region").is_none());
+    }
+}
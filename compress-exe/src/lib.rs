@@ -0,0 +1,66 @@
+// compress-exe\src\lib.rs
+
+// This program compresses all executable files in a specified directory (and its subdirectories) using UPX.
+//
+// The program takes one optional command-line argument: the path to the directory containing the executables,
+// or a single executable. If no argument is provided, it defaults to `--dir` or the current directory.
+//
+// It recursively iterates over each entry in the specified directory and its subdirectories.
+// If an entry is a file with a ".exe" extension, it attempts to compress it using the "upx" command
+// with the "--best" option for maximum compression.
+//
+// If the compression fails for any executable, it prints an error message to the standard error.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::path::PathBuf;
+use tokio::process::Command as AsyncCommand;
+use dataset_tools::{ walk_directory_with, CommonArgs };
+use anyhow::{ Context, Result };
+
+/// Compress every .exe file in a directory with UPX
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Executable or directory of executables to compress
+    pub target: Option<PathBuf>,
+}
+
+async fn compress_exe(path: PathBuf) -> Result<()> {
+    println!("Compressing: {}", path.display());
+    let status = AsyncCommand::new("upx")
+        .arg("--best")
+        .arg(&path)
+        .status().await
+        .context("Failed to run UPX command")?;
+    if !status.success() {
+        anyhow::bail!("UPX exited with {status}");
+    }
+    Ok(())
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any executable fails to compress.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let target_path = common.dir_or(args.target);
+
+    if target_path.is_file() {
+        if target_path.extension().is_some_and(|ext| ext == "exe") {
+            compress_exe(target_path).await?;
+        } else {
+            println!("The specified file is not an .exe file.");
+        }
+    } else if target_path.is_dir() {
+        walk_directory_with(&target_path, "exe", &common.walk_options(), compress_exe)
+            .await?
+            .report()?;
+    } else {
+        println!("The specified path does not exist or is not accessible.");
+    }
+
+    Ok(())
+}
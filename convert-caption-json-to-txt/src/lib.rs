@@ -0,0 +1,85 @@
+// convert-caption-json-to-txt\src\lib.rs
+//
+// Converts the json created by JTP_PILOT2-2-e3-vit_so400m_patch14_siglip_384
+// to caption files.
+//
+// The input is either a directory, which is searched for .json files, or a text file
+// listing one JSON file per line. Tags are kept above the `tag-threshold` of the nearest
+// `dataset-tools.toml`, 0.2 by default, or the `--threshold` option. Tags removed by the
+// `ignored-tags` and `tag-rules` of the config, or by `--rules <file>`, are left out.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::{ path::{ Path, PathBuf }, sync::Arc };
+use anyhow::{ bail, Result };
+use dataset_tools::{
+    process_json_to_caption,
+    walk_directory_with,
+    CommonArgs,
+    FilterArgs,
+    TagFilter,
+};
+use tokio::fs::File;
+use tokio::io::{ AsyncBufReadExt, BufReader };
+
+/// Convert tagger JSON output to caption files
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory of JSON files, or a text file listing JSON files
+    pub input: Option<PathBuf>,
+
+    /// Minimum probability of a tag, overrides `tag-threshold` from the config
+    #[arg(short, long)]
+    pub threshold: Option<f64>,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the input is neither a directory nor a file, or cannot be read.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let input_path = common.dir_or(args.input);
+    let mut config = common.config(&input_path)?;
+    if let Some(threshold) = args.threshold {
+        config.tag_threshold = threshold;
+    }
+    let filter = TagFilter::from_config(&config, &args.filter)?;
+
+    if input_path.is_dir() {
+        let config = Arc::new(config);
+        let filter = Arc::new(filter);
+        walk_directory_with(&input_path, "json", &common.walk_options(), |path| {
+            let config = Arc::clone(&config);
+            let filter = Arc::clone(&filter);
+            async move {
+                process_json_to_caption(&path, &config, &filter).await?;
+                Ok(())
+            }
+        }).await?.report()?;
+    } else if input_path.is_file() {
+        let file = File::open(&input_path).await?;
+        let reader = BufReader::new(file);
+        let mut lines = reader.lines();
+
+        while let Some(line) = lines.next_line().await? {
+            let path = Path::new(&line);
+            if path.exists() {
+                if let Err(e) = process_json_to_caption(path, &config, &filter).await {
+                    eprintln!("Error processing {}: {}", path.display(), e);
+                }
+            } else {
+                eprintln!("File not found: {line}");
+            }
+        }
+    } else {
+        bail!("Invalid input: not a directory or file");
+    }
+
+    Ok(())
+}
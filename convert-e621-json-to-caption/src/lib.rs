@@ -0,0 +1,1051 @@
+// convert-e621-json-to-caption\src\lib.rs
+
+//! # e621.net JSON to Caption File Converter
+//!
+//! This script processes JSON files from e621.net, extracting "post" data to create caption files.
+//! It navigates through a directory and its subdirectories, reads each JSON file, and generates
+//! a caption file containing the post's rating and tags.
+//!
+//! With `--source danbooru`, `gelbooru` or `rule34`, the metadata of those boorus is read
+//! instead: Danbooru JSON with its `tag_string_<category>` fields, or Gelbooru and Rule34 JSON
+//! and XML dumps, whose tags have no category and count as general. Every option works the same
+//! for every source.
+//!
+//! Tags that match the `ignored-tags` patterns of the nearest `dataset-tools.toml` are ignored,
+//! and artists get its `artist-prefix`. Both can be overridden on the command line. Tags are
+//! also filtered by the `tag-rules` file of the config or the one given with `--rules <file>`.
+//!
+//! With the `tag_aliases` export of the e621 database given as `--aliases <csv>`, aliased tags
+//! are renamed to their canonical tag. With the `tag_implications` export given as
+//! `--implications <csv>`, `--drop-implied` leaves out tags implied by another tag of the post.
+//!
+//! The tags follow the rating grouped by category, in the `category-order` of the config:
+//! artist, character, species, general and meta by default, then any other category in
+//! alphabetical order. Categories in `excluded-categories` are left out, and `category-limits`
+//! caps the number of tags taken from a category.
+//!
+//! The `caption-style` of the config, or `--style`, decides how the rating is written and
+//! whether a quality tag comes first. `plain` writes `safe`, `questionable` or `nsfw`. `pony`
+//! writes `score_9` to `score_4` and `rating_safe`, `rating_questionable` or `rating_explicit`,
+//! as Pony Diffusion and similar SDXL models expect. `custom` fills in the `rating-template`
+//! and `score-template`. Quality scores rank the posts of the dataset by score and favorites,
+//! which takes a first pass over every metadata file.
+//!
+//! Captions are named after the file name in the URL of each post, which only finds images
+//! saved under that name. With `--match hash`, every image of the directory is hashed instead
+//! and each post goes to the images whose MD5 is the `md5` of the post, whatever their name,
+//! with the caption next to the image. Metadata without an image and images without metadata
+//! are listed, as are images named after a post whose content does not match its MD5, which
+//! usually means a corrupt download.
+//!
+//! With `--sentences`, natural-language sentences follow the tags, in the order given:
+//! `characters` names the characters of the post, `pools` the pools it is part of, and
+//! `description` is the description of the post with its `DText` markup, links and quotes
+//! removed, cut to the whole sentences that fit `--description-length` characters. e621 JSON
+//! only lists pool IDs, so pool names need metadata that has them, such as that of gallery-dl.
+//!
+//! Instead of JSON files, `--posts <csv>` reads the `posts` export of the e621 database
+//! together with its `tags` export, given as `--tags <csv>`, for the tag categories. Every
+//! image of the directory is hashed, the export is streamed row by row and the posts whose MD5
+//! matches an image are converted into a caption next to that image.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+mod quality;
+
+use anyhow::Context;
+use dataset_tools::{
+    dtext,
+    e621::{ read_posts, CaptionStyle, TagCategories },
+    file_md5,
+    read_file_content,
+    walk_directory_with,
+    write_to_file,
+    Booru,
+    BooruPost,
+    BooruSource,
+    Caption,
+    CommonArgs,
+    Config,
+    Dataset,
+    FilterArgs,
+    Rating,
+    Skipped,
+    TagFilter,
+    TagRelations,
+    walk_paths,
+    WalkSummary,
+};
+use log::{ debug, info };
+use std::{
+    collections::{ BTreeMap, HashMap, HashSet },
+    fs::File,
+    io::Read,
+    path::{ Path, PathBuf },
+    sync::Arc,
+};
+use tokio::{ sync::Mutex, task };
+
+use quality::QualityBuckets;
+
+/// Convert e621 post JSON files, or the metadata of other boorus, to caption files
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for JSON files, or for images with `--posts`
+    pub directory: Option<PathBuf>,
+
+    /// The booru the metadata files come from
+    #[arg(long, value_enum, default_value_t = Booru::E621)]
+    pub source: Booru,
+
+    /// How posts are matched to their images, `hash` also finds renamed images
+    #[arg(
+        long = "match",
+        value_enum,
+        value_name = "BY",
+        default_value_t = MatchBy::Stem,
+        conflicts_with = "posts"
+    )]
+    pub match_by: MatchBy,
+
+    /// The `posts` export of the e621 database, matched to the images by MD5 instead of
+    /// reading JSON files
+    #[arg(long, value_name = "CSV", requires = "tags", conflicts_with = "source")]
+    pub posts: Option<PathBuf>,
+
+    /// The `tags` export of the e621 database, for the tag categories of `--posts`
+    #[arg(long, value_name = "CSV", requires = "posts")]
+    pub tags: Option<PathBuf>,
+
+    /// Pattern of tags to leave out, can be repeated, overrides `ignored-tags` from the config
+    #[arg(long = "ignore-tag", value_name = "REGEX")]
+    pub ignored_tags: Option<Vec<String>>,
+
+    /// Text put in front of artist names, overrides `artist-prefix` from the config
+    #[arg(long)]
+    pub artist_prefix: Option<String>,
+
+    /// The `tag_aliases` export of the e621 database, aliased tags are renamed
+    #[arg(long, value_name = "CSV")]
+    pub aliases: Option<PathBuf>,
+
+    /// The `tag_implications` export of the e621 database, used by `--drop-implied`
+    #[arg(long, value_name = "CSV")]
+    pub implications: Option<PathBuf>,
+
+    /// Leave out tags implied by another tag of the post, such as `canine` next to `wolf`
+    #[arg(long, requires = "implications")]
+    pub drop_implied: bool,
+
+    /// Order of the tag categories after the rating, overrides `category-order` from the config
+    #[arg(long, value_name = "CATEGORIES", value_delimiter = ',')]
+    pub category_order: Option<Vec<String>>,
+
+    /// Tag category to leave out, can be repeated, overrides `excluded-categories` from the
+    /// config
+    #[arg(long = "exclude-category", value_name = "CATEGORY")]
+    pub excluded_categories: Option<Vec<String>>,
+
+    /// Maximum number of tags from a category, can be repeated, adds to `category-limits` from
+    /// the config
+    #[arg(long = "category-limit", value_name = "CATEGORY=N", value_parser = parse_category_limit)]
+    pub category_limits: Vec<(String, usize)>,
+
+    /// How the rating and quality of a post are written, overrides `caption-style` from the
+    /// config
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub style: Option<CaptionStyle>,
+
+    /// Rating tag of the custom style, `{rating}` becomes `safe`, `questionable` or `explicit`,
+    /// overrides `rating-template` from the config
+    #[arg(long, value_name = "TEMPLATE")]
+    pub rating_template: Option<String>,
+
+    /// Quality tag of the custom style, `{score}` becomes 9 for the best posts down to 4,
+    /// overrides `score-template` from the config
+    #[arg(long, value_name = "TEMPLATE")]
+    pub score_template: Option<String>,
+
+    /// Sentences written after the tags, in the order given
+    #[arg(long, value_enum, value_name = "PARTS", value_delimiter = ',')]
+    pub sentences: Vec<SentencePart>,
+
+    /// Maximum length of the description in characters, longer ones are cut to whole sentences
+    #[arg(long, value_name = "CHARS", default_value_t = 300)]
+    pub description_length: usize,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+/// How posts are matched to their images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MatchBy {
+    /// The file name in the URL of the post, next to the metadata file
+    Stem,
+    /// The MD5 hash of the image, wherever it is and whatever its name
+    Hash,
+}
+
+/// A sentence written after the tags of a caption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SentencePart {
+    /// The description of the post, without its markup
+    Description,
+    /// The names of the pools of the post
+    Pools,
+    /// The names of the characters of the post
+    Characters,
+}
+
+/// Parses a `category=n` limit.
+fn parse_category_limit(value: &str) -> Result<(String, usize), String> {
+    let (category, limit) = value.split_once('=').ok_or("expected `category=n`")?;
+    let limit = limit.trim().parse().map_err(|e| format!("invalid limit `{limit}`: {e}"))?;
+    Ok((category.trim().to_string(), limit))
+}
+
+/// How posts are converted, from the config and the command line.
+struct Settings {
+    /// The ignored tags and tag rules.
+    filter: TagFilter,
+    artist_prefix: String,
+    caption_extension: String,
+    relations: TagRelations,
+    drop_implied: bool,
+    category_order: Vec<String>,
+    excluded_categories: Vec<String>,
+    category_limits: BTreeMap<String, usize>,
+    caption_style: CaptionStyle,
+    rating_template: Option<String>,
+    score_template: Option<String>,
+    sentences: Vec<SentencePart>,
+    description_length: usize,
+    /// The quality thresholds of the dataset, once the first pass ran.
+    quality: Option<QualityBuckets>,
+}
+
+impl Settings {
+    fn new(args: Args, mut config: Config) -> anyhow::Result<Self> {
+        let relations = TagRelations::load(args.aliases.as_deref(), args.implications.as_deref())?;
+        if let Some(ignored_tags) = args.ignored_tags {
+            config.ignored_tags = ignored_tags;
+        }
+        let filter = TagFilter::from_config(&config, &args.filter)?;
+        let mut category_limits = config.category_limits;
+        category_limits.extend(args.category_limits);
+        Ok(Self {
+            filter,
+            artist_prefix: args.artist_prefix.unwrap_or(config.artist_prefix),
+            caption_extension: config.caption_extension,
+            relations,
+            drop_implied: args.drop_implied,
+            category_order: args.category_order.unwrap_or(config.category_order),
+            excluded_categories: args.excluded_categories.unwrap_or(config.excluded_categories),
+            category_limits,
+            caption_style: args.style.unwrap_or(config.caption_style),
+            rating_template: args.rating_template.or(config.rating_template),
+            score_template: args.score_template.or(config.score_template),
+            sentences: args.sentences,
+            description_length: args.description_length,
+            quality: None,
+        })
+    }
+
+    /// Returns `true` if the caption style ranks posts with a quality tag.
+    fn uses_scores(&self) -> bool {
+        match self.caption_style {
+            CaptionStyle::Plain => false,
+            CaptionStyle::Pony => true,
+            CaptionStyle::Custom => self.score_template.is_some(),
+        }
+    }
+
+    /// Returns the rating tag of a rating, if the caption style has one.
+    fn rating_tag(&self, rating: Rating) -> Option<String> {
+        let tag = match self.caption_style {
+            CaptionStyle::Plain if rating == Rating::Explicit => "nsfw".to_string(),
+            CaptionStyle::Plain => rating.name().to_string(),
+            CaptionStyle::Pony => format!("rating_{}", rating.name()),
+            CaptionStyle::Custom => self.rating_template.as_ref()?.replace(
+                "{rating}",
+                rating.name()
+            ),
+        };
+        Some(tag).filter(|tag| !tag.is_empty())
+    }
+
+    /// Returns the quality tag of a post, if the caption style has one.
+    fn score_tag(&self, post: &BooruPost) -> Option<String> {
+        let score = self.quality.as_ref()?.score(post.score, post.favorites);
+        let tag = match self.caption_style {
+            CaptionStyle::Plain => {
+                return None;
+            }
+            CaptionStyle::Pony => format!("score_{score}"),
+            CaptionStyle::Custom => self.score_template
+                .as_ref()?
+                .replace("{score}", &score.to_string()),
+        };
+        Some(tag).filter(|tag| !tag.is_empty())
+    }
+
+    /// Returns the categories of a post that go into the caption, in caption order.
+    fn ordered_categories<'a>(
+        &self,
+        categories: impl Iterator<Item = &'a String>
+    ) -> Vec<&'a str> {
+        let rank = |category: &str| {
+            self.category_order.iter().position(|c| c == category).unwrap_or(usize::MAX)
+        };
+        let mut categories: Vec<&str> = categories
+            .map(String::as_str)
+            .filter(|category| !self.excluded_categories.iter().any(|c| c == category))
+            .collect();
+        categories.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+        categories
+    }
+
+    /// Returns the maximum number of tags taken from a category.
+    fn category_limit(&self, category: &str) -> usize {
+        self.category_limits.get(category).copied().unwrap_or(usize::MAX)
+    }
+}
+
+/// Joins names into prose: `a`, `a and b` or `a, b and c`.
+fn join_names(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.clone(),
+        [rest @ .., last] => format!("{} and {last}", rest.join(", ")),
+    }
+}
+
+/// Writes a character tag as a name, `krystal_(star_fox)` as `Krystal (Star Fox)`.
+fn character_name(tag: &str) -> String {
+    tag.replace('_', " ")
+        .replace(" (character)", "")
+        .split(' ')
+        .map(|word| {
+            let (prefix, word) = word.split_at(word.starts_with('(').into());
+            let mut chars = word.chars();
+            let first = chars.next().map(char::to_uppercase).into_iter().flatten();
+            format!("{prefix}{}{}", first.collect::<String>(), chars.as_str())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes the sentences of a post that follow its tags.
+///
+/// # Returns
+///
+/// * `String` - The sentences of the parts in `settings.sentences`, empty if the post has none.
+fn post_sentences(post: &BooruPost, settings: &Settings) -> String {
+    let mut sentences = Vec::new();
+    for part in &settings.sentences {
+        let sentence = match part {
+            SentencePart::Description => post.description
+                .as_deref()
+                .map(|text| dtext::to_sentences(text, settings.description_length))
+                .unwrap_or_default(),
+            SentencePart::Pools => match post.pools.as_slice() {
+                [] => String::new(),
+                [pool] => format!("From the pool {pool}."),
+                pools => format!("From the pools {}.", join_names(pools)),
+            },
+            SentencePart::Characters => {
+                let names: Vec<String> = post.tags
+                    .get("character")
+                    .into_iter()
+                    .flatten()
+                    .map(|tag| settings.relations.canonical(tag))
+                    .filter(|tag| settings.filter.allows(tag))
+                    .map(character_name)
+                    .collect();
+                if names.is_empty() {
+                    String::new()
+                } else {
+                    format!("Featuring {}.", join_names(&names))
+                }
+            }
+        };
+        if !sentence.is_empty() {
+            sentences.push(sentence);
+        }
+    }
+    sentences.join(" ")
+}
+
+/// Processes and formats the tags of a post.
+///
+/// # Arguments
+///
+/// * `tags` - The tags of the post by category.
+/// * `settings` - The ignored tag patterns and artist prefix.
+///
+/// # Returns
+///
+/// * `Vec<String>` - A vector of strings containing processed and formatted tags.
+fn process_tags(tags: &BTreeMap<String, Vec<String>>, settings: &Settings) -> Vec<String> {
+    debug!("Starting process_tags with {} categories", tags.len());
+    let mut processed_tags = Vec::new();
+
+    // Resolved over the whole post, as a tag can imply one from another category. Every tag
+    // is taken out once it is used, so aliases of the same tag end up in it once.
+    let names: Vec<&String> = tags.values().flatten().collect();
+    let mut kept: HashSet<String> = settings.relations
+        .resolve(&names, settings.drop_implied)
+        .into_iter()
+        .collect();
+
+    for category in settings.ordered_categories(tags.keys()) {
+        debug!("Processing category: {category}");
+        let limit = settings.category_limit(category);
+        let tags_array = &tags[category];
+        debug!("Found {} tags in category", tags_array.len());
+
+        let category_tags: Vec<String> = if category == "artist" {
+            tags_array
+                .iter()
+                .map(|tag| settings.relations.canonical(tag))
+                .filter(|&tag| kept.remove(tag) && settings.filter.allows(tag))
+                .take(limit)
+                .map(|tag| {
+                    format!(
+                        "{}{}",
+                        settings.artist_prefix,
+                        tag.replace('_', " ").replace(" (artist)", "")
+                    )
+                })
+                .collect()
+        } else {
+            tags_array
+                .iter()
+                .map(|tag| settings.relations.canonical(tag))
+                .filter(|&tag| {
+                    kept.remove(tag) &&
+                        tag.to_lowercase() != "artist" &&
+                        settings.filter.allows(tag)
+                })
+                .take(limit)
+                .map(|tag| {
+                    tag.replace('_', " ")
+                    // tag.replace('(', r"\(").replace(')', r"\)")
+                })
+                .collect()
+        };
+
+        debug!("Processed {} tags in category", category_tags.len());
+        processed_tags.extend(category_tags);
+    }
+
+    debug!("Final processed tags count: {}", processed_tags.len());
+    processed_tags
+}
+
+/// Builds the caption of a post: its quality and rating tags followed by its processed tags.
+///
+/// # Returns
+///
+/// * `Option<Caption>` - The caption, or `None` if no tags of the post are left.
+fn post_caption(post: &BooruPost, settings: &Settings) -> Option<Caption> {
+    debug!("Rating: {}", post.rating.name());
+
+    // Tags are written as the booru has them, without escaping their parentheses
+    let mut caption = Caption::default();
+    caption.tags = settings
+        .score_tag(post)
+        .into_iter()
+        .chain(settings.rating_tag(post.rating))
+        .collect();
+    debug!("Initial caption content: {caption}");
+
+    let processed_tags = process_tags(&post.tags, settings);
+    debug!("Processed tags: {processed_tags:?}");
+    if processed_tags.is_empty() {
+        debug!("No processed tags found!");
+        return None;
+    }
+    caption.tags.extend(processed_tags);
+    caption.sentences = post_sentences(post, settings);
+    Some(caption)
+}
+
+/// Writes a caption file and prints its content.
+async fn write_caption(caption_path: &Path, caption: &Caption) -> anyhow::Result<()> {
+    let caption_content = caption.to_string();
+
+    println!("{}", "-".repeat(50));
+    println!("Caption file: {}", caption_path.display());
+    println!("Tags: {caption_content}");
+    println!("{}", "-".repeat(50));
+
+    debug!("Attempting to write file...");
+    write_to_file(caption_path, &caption_content).await?;
+    debug!("Successfully wrote file");
+    Ok(())
+}
+
+/// Reads the posts of a metadata file. Files that are not post metadata, such as tagger
+/// output next to the posts, return `Skipped`, so walks count them as skipped.
+async fn read_metadata(path: &Path, source: &dyn BooruSource) -> anyhow::Result<Vec<BooruPost>> {
+    let content = read_file_content(path).await?;
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    let posts = source
+        .parse(&content, extension)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    posts.ok_or_else(|| Skipped("No post data found".to_string()).into())
+}
+
+/// Processes a single metadata file.
+///
+/// # Arguments
+///
+/// * `file_path` - A `PathBuf` representing the file path.
+/// * `source` - The booru the file comes from.
+/// * `settings` - How tags are converted and where the caption goes.
+///
+/// # Returns
+///
+/// * `anyhow::Result<()>` - The result of the file processing operation.
+async fn process_file(
+    file_path: PathBuf,
+    source: Arc<dyn BooruSource>,
+    settings: Arc<Settings>
+) -> anyhow::Result<()> {
+    info!("Processing file: {}", file_path.display());
+
+    // Captions go next to the metadata, named after the image of the post
+    for post in read_metadata(&file_path, source.as_ref()).await? {
+        let Some(file_stem) = &post.file_stem else {
+            debug!("No file URL found in post!");
+            continue;
+        };
+        let caption_path = file_path.with_file_name(
+            format!("{file_stem}.{}", settings.caption_extension)
+        );
+        debug!("Caption path will be: {}", caption_path.display());
+
+        if let Some(caption) = post_caption(&post, &settings) {
+            write_caption(&caption_path, &caption).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Collects the score and favorites of every post, the first pass of the quality tags.
+async fn collect_scores(
+    directory: &Path,
+    source: &Arc<dyn BooruSource>,
+    common: &CommonArgs
+) -> anyhow::Result<Vec<(i64, i64)>> {
+    let posts = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&posts);
+    let files = source.extensions();
+    walk_directory_with(directory, files, &common.walk_options(), move |path| {
+        let posts = Arc::clone(&collected);
+        let source = Arc::clone(source);
+        async move {
+            // Files that cannot be read are reported by the conversion itself
+            if let Ok(metadata) = read_metadata(&path, source.as_ref()).await {
+                let scores = metadata.iter().map(|post| (post.score, post.favorites));
+                posts.lock().await.extend(scores);
+            }
+            Ok(())
+        }
+    }).await?;
+    let scores = std::mem::take(&mut *posts.lock().await);
+    Ok(scores)
+}
+
+/// Hashes every image of a dataset.
+///
+/// # Returns
+///
+/// * `HashMap<String, Vec<PathBuf>>` - The images by the MD5 of their content, as copies of an
+///   image share a hash.
+async fn hash_images(
+    dataset: &Dataset,
+    common: &CommonArgs
+) -> anyhow::Result<HashMap<String, Vec<PathBuf>>> {
+    let images = Arc::new(Mutex::new(HashMap::new()));
+    let collected = Arc::clone(&images);
+    let paths = dataset.iter().map(|sample| sample.image.clone());
+    walk_paths(paths, &common.walk_options(), move |path| {
+        let images = Arc::clone(&collected);
+        async move {
+            let md5 = file_md5(&path).await?;
+            images.lock().await.entry(md5).or_insert_with(Vec::new).push(path);
+            Ok(())
+        }
+    }).await.report()?;
+    let images = std::mem::take(&mut *images.lock().await);
+    Ok(images)
+}
+
+/// Streams a `posts` export and takes the posts of the hashed images out of `images`, which is
+/// left with the images that have no post.
+///
+/// # Returns
+///
+/// * `Vec<(Vec<PathBuf>, BooruPost)>` - The images of every matched post and the post.
+fn match_posts(
+    posts: impl Read,
+    categories: &TagCategories,
+    images: &mut HashMap<String, Vec<PathBuf>>
+) -> anyhow::Result<Vec<(Vec<PathBuf>, BooruPost)>> {
+    let mut matched = Vec::new();
+    for post in read_posts(posts) {
+        if images.is_empty() {
+            break;
+        }
+        let post = post?;
+        if let Some(paths) = images.remove(&post.md5.to_lowercase()) {
+            matched.push((paths, post.to_post(categories)));
+        }
+    }
+    Ok(matched)
+}
+
+/// Reads the posts of every metadata file below a directory, in order of their files.
+async fn read_all_posts(
+    directory: &Path,
+    source: &Arc<dyn BooruSource>,
+    common: &CommonArgs
+) -> anyhow::Result<(Vec<(PathBuf, BooruPost)>, WalkSummary)> {
+    let posts = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&posts);
+    let files = source.extensions();
+    let summary = walk_directory_with(directory, files, &common.walk_options(), move |path| {
+        let posts = Arc::clone(&collected);
+        let source = Arc::clone(source);
+        async move {
+            let metadata = read_metadata(&path, source.as_ref()).await?;
+            posts.lock().await.extend(metadata.into_iter().map(|post| (path.clone(), post)));
+            Ok(())
+        }
+    }).await?;
+    let mut posts = std::mem::take(&mut *posts.lock().await);
+    // Stable, so the posts of a file keep their order
+    posts.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok((posts, summary))
+}
+
+/// How the posts of the metadata files matched the images of the directory.
+#[derive(Debug, Default)]
+struct HashMatches {
+    /// The images of every matched post and the post.
+    matched: Vec<(Vec<PathBuf>, BooruPost)>,
+    /// The metadata file and ID of every post without an image.
+    missing: Vec<(PathBuf, Option<u64>)>,
+    /// Images named after a post, the MD5 of the post and the MD5 of the image.
+    corrupt: Vec<(PathBuf, String, String)>,
+    /// Images without a post.
+    unmatched: Vec<PathBuf>,
+}
+
+/// Matches posts to the hashed images by their MD5. Images left without a post but named after
+/// one of the posts without an image are corrupt copies of it.
+fn match_by_hash(
+    posts: Vec<(PathBuf, BooruPost)>,
+    mut images: HashMap<String, Vec<PathBuf>>
+) -> HashMatches {
+    let mut matches = HashMatches::default();
+    let mut seen = HashSet::new();
+    let mut without_image = Vec::new();
+    for (metadata, post) in posts {
+        let Some(md5) = post.md5.clone() else {
+            matches.missing.push((metadata, post.id));
+            continue;
+        };
+        if let Some(paths) = images.remove(&md5) {
+            seen.insert(md5);
+            matches.matched.push((paths, post));
+        } else if !seen.contains(&md5) {
+            without_image.push((metadata, post, md5));
+        }
+    }
+
+    let mut named = HashMap::new();
+    for (md5, paths) in &images {
+        for path in paths {
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                named.insert(stem.to_lowercase(), (path, md5));
+            }
+        }
+    }
+    let mut corrupt = HashSet::new();
+    for (metadata, post, md5) in without_image {
+        let image = [post.file_stem.as_deref(), Some(md5.as_str())]
+            .into_iter()
+            .flatten()
+            .find_map(|name| named.get(&name.to_lowercase()));
+        if let Some(&(path, found)) = image {
+            corrupt.insert(path.clone());
+            matches.corrupt.push((path.clone(), md5, found.clone()));
+        } else {
+            matches.missing.push((metadata, post.id));
+        }
+    }
+
+    matches.unmatched = images
+        .values()
+        .flatten()
+        .filter(|&path| !corrupt.contains(path))
+        .cloned()
+        .collect();
+    matches.unmatched.sort();
+    matches.corrupt.sort();
+    matches
+}
+
+/// Ranks the matched posts by score and favorites, if the caption style needs it, and writes
+/// the caption of every post next to each of its images, over the caption the image already
+/// has in the dataset, such as `image.png.txt`.
+async fn write_image_captions(
+    matched: &[(Vec<PathBuf>, BooruPost)],
+    dataset: &Dataset,
+    settings: &mut Settings
+) -> anyhow::Result<()> {
+    if settings.uses_scores() {
+        let scores: Vec<(i64, i64)> = matched
+            .iter()
+            .map(|(_, post)| (post.score, post.favorites))
+            .collect();
+        settings.quality = Some(QualityBuckets::new(&scores));
+    }
+    let captions: HashMap<&Path, &Path> = dataset
+        .iter()
+        .filter_map(|sample| {
+            let caption = sample.caption_with_extension(&settings.caption_extension)?;
+            Some((sample.image.as_path(), caption))
+        })
+        .collect();
+    for (paths, post) in matched {
+        let Some(caption) = post_caption(post, settings) else {
+            continue;
+        };
+        for path in paths {
+            let caption_path = match captions.get(path.as_path()) {
+                Some(caption_path) => caption_path.to_path_buf(),
+                None => path.with_extension(&settings.caption_extension),
+            };
+            write_caption(&caption_path, &caption).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists the images without a post and prints how many images have one.
+fn report_unmatched(
+    unmatched: &[PathBuf],
+    matched: &[(Vec<PathBuf>, BooruPost)],
+    image_count: usize
+) {
+    for path in unmatched {
+        println!("No post found for {}", path.display());
+    }
+    let found: usize = matched.iter().map(|(paths, _)| paths.len()).sum();
+    println!("Found the posts of {found} of {image_count} images");
+}
+
+/// Converts the posts of the metadata files whose MD5 matches an image of the directory.
+async fn convert_by_hash(
+    directory: &Path,
+    source: &Arc<dyn BooruSource>,
+    mut settings: Settings,
+    common: &CommonArgs
+) -> anyhow::Result<()> {
+    let dataset = Dataset::scan_with(directory, &common.walk_options()).await?;
+    let images = hash_images(&dataset, common).await?;
+    let image_count = images.values().map(Vec::len).sum::<usize>();
+    println!("Hashed {image_count} images");
+
+    let (posts, summary) = read_all_posts(directory, source, common).await?;
+    let matches = match_by_hash(posts, images);
+    write_image_captions(&matches.matched, &dataset, &mut settings).await?;
+
+    for (metadata, id) in &matches.missing {
+        match id {
+            Some(id) => println!("No image found for post {id} of {}", metadata.display()),
+            None => println!("No image found for {}", metadata.display()),
+        }
+    }
+    for (path, expected, found) in &matches.corrupt {
+        println!(
+            "MD5 mismatch for {}: expected {expected}, found {found}, likely a corrupt download",
+            path.display()
+        );
+    }
+    report_unmatched(&matches.unmatched, &matches.matched, image_count);
+    summary.report()
+}
+
+/// Converts the posts of the e621 database exports whose MD5 matches an image of the directory.
+async fn convert_export(
+    directory: &Path,
+    posts: PathBuf,
+    tags: PathBuf,
+    mut settings: Settings,
+    common: &CommonArgs
+) -> anyhow::Result<()> {
+    let dataset = Dataset::scan_with(directory, &common.walk_options()).await?;
+    let mut images = hash_images(&dataset, common).await?;
+    let image_count = images.values().map(Vec::len).sum::<usize>();
+    println!("Hashed {image_count} images, searching {}", posts.display());
+
+    let (matched, unmatched) = task::spawn_blocking(move || {
+        let categories = TagCategories::load(&tags)?;
+        let file = File::open(&posts)
+            .with_context(|| format!("Failed to open {}", posts.display()))?;
+        let matched = match_posts(file, &categories, &mut images)
+            .with_context(|| format!("Invalid posts {}", posts.display()))?;
+        anyhow::Ok((matched, images))
+    }).await??;
+    write_image_captions(&matched, &dataset, &mut settings).await?;
+
+    let mut unmatched: Vec<PathBuf> = unmatched.into_values().flatten().collect();
+    unmatched.sort();
+    report_unmatched(&unmatched, &matched, image_count);
+    Ok(())
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any JSON file fails to convert.
+pub async fn run(args: Args, common: &CommonArgs) -> anyhow::Result<()> {
+    common.init()?;
+    let root_directory = common.dir_or(args.directory.clone());
+    debug!("Root directory: {}", root_directory.display());
+    let config = common.config(&root_directory)?;
+    let export = args.posts.clone().zip(args.tags.clone());
+    let match_by = args.match_by;
+    let source: Arc<dyn BooruSource> = Arc::from(args.source.source());
+    let mut settings = Settings::new(args, config)?;
+    if let Some((posts, tags)) = export {
+        return convert_export(&root_directory, posts, tags, settings, common).await;
+    }
+    if match_by == MatchBy::Hash {
+        return convert_by_hash(&root_directory, &source, settings, common).await;
+    }
+    if settings.uses_scores() {
+        let scores = collect_scores(&root_directory, &source, common).await?;
+        let quality = QualityBuckets::new(&scores);
+        println!("Ranked {} posts by score and favorites", quality.len());
+        settings.quality = Some(quality);
+    }
+    let settings = Arc::new(settings);
+
+    let files = source.extensions();
+    let summary = walk_directory_with(&root_directory, files, &common.walk_options(), |path| {
+        process_file(path, Arc::clone(&source), Arc::clone(&settings))
+    }).await?;
+    println!("Finished processing");
+    summary.report()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings(config: Config) -> Settings {
+        Settings {
+            filter: TagFilter::default(),
+            artist_prefix: config.artist_prefix,
+            caption_extension: config.caption_extension,
+            relations: TagRelations::default(),
+            drop_implied: false,
+            category_order: config.category_order,
+            excluded_categories: config.excluded_categories,
+            category_limits: config.category_limits,
+            caption_style: config.caption_style,
+            rating_template: config.rating_template,
+            score_template: config.score_template,
+            sentences: Vec::new(),
+            description_length: 300,
+            quality: None,
+        }
+    }
+
+    #[test]
+    fn test_category_order() {
+        let settings = settings(Config {
+            excluded_categories: vec!["lore".to_string()],
+            category_limits: BTreeMap::from([("general".to_string(), 2)]),
+            ..Config::default()
+        });
+        let tags: BTreeMap<String, Vec<String>> = serde_json::from_value(json!({
+            "copyright": ["zootopia"],
+            "general": ["fur", "outside", "solo"],
+            "lore": ["male_(lore)"],
+            "meta": ["hi_res"],
+            "species": ["red_fox"],
+            "character": ["nick_wilde"],
+            "artist": ["someone_(artist)"],
+        })).unwrap();
+
+        assert_eq!(
+            process_tags(&tags, &settings),
+            ["by someone", "nick wilde", "red fox", "fur", "outside", "hi res", "zootopia"]
+        );
+    }
+
+    #[test]
+    fn test_match_posts() {
+        let posts = "id,md5,rating,tag_string,fav_count,score,file_ext
+1,aaaa,s,fur solo,1,2,png
+2,BBBB,e,fur duo,3,4,jpg
+3,cccc,q,fur,5,6,png
+";
+        let mut images = HashMap::from([
+            ("bbbb".to_string(), vec![PathBuf::from("b.jpg"), PathBuf::from("copy/b.jpg")]),
+            ("dddd".to_string(), vec![PathBuf::from("d.png")]),
+        ]);
+        let categories = TagCategories::default();
+        let matched = match_posts(posts.as_bytes(), &categories, &mut images).unwrap();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].0.len(), 2);
+        assert_eq!(matched[0].1.tags["general"], ["fur", "duo"]);
+        assert_eq!(images.keys().collect::<Vec<_>>(), ["dddd"]);
+
+        let caption = post_caption(&matched[0].1, &settings(Config::default())).unwrap();
+        assert_eq!(caption.to_string(), "nsfw, fur, duo");
+    }
+
+    #[tokio::test]
+    async fn test_other_json_is_skipped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let post = json!({ "post": { "id": 1, "tags": { "general": ["fur"] } } });
+        std::fs::write(dir.path().join("1.json"), post.to_string()).unwrap();
+        std::fs::write(dir.path().join("tags.json"), r#"{"fur": 0.9}"#).unwrap();
+
+        let source: Arc<dyn BooruSource> = Arc::from(Booru::E621.source());
+        let (posts, summary) = read_all_posts(dir.path(), &source, &CommonArgs::default())
+            .await
+            .unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!((summary.processed, summary.skipped), (1, 1));
+        assert!(summary.is_success());
+    }
+
+    #[test]
+    fn test_match_by_hash() {
+        let post = |id, md5: &str, file_stem: &str| BooruPost {
+            id: Some(id),
+            md5: Some(md5.to_string()),
+            file_stem: Some(file_stem.to_string()),
+            ..BooruPost::default()
+        };
+        let posts = vec![
+            (PathBuf::from("1.json"), post(1, "aaaa", "aaaa")),
+            (PathBuf::from("1 copy.json"), post(1, "aaaa", "aaaa")),
+            (PathBuf::from("2.json"), post(2, "bbbb", "bbbb")),
+            (PathBuf::from("3.json"), post(3, "cccc", "cccc")),
+        ];
+        let images = HashMap::from([
+            ("aaaa".to_string(), vec![PathBuf::from("renamed.png")]),
+            ("ffff".to_string(), vec![PathBuf::from("BBBB.png")]),
+            ("eeee".to_string(), vec![PathBuf::from("other.png")]),
+        ]);
+
+        let matches = match_by_hash(posts, images);
+        assert_eq!(matches.matched.len(), 1);
+        assert_eq!(matches.matched[0].0, [PathBuf::from("renamed.png")]);
+        assert_eq!(matches.missing, [(PathBuf::from("3.json"), Some(3))]);
+        assert_eq!(
+            matches.corrupt,
+            [(PathBuf::from("BBBB.png"), "bbbb".to_string(), "ffff".to_string())]
+        );
+        assert_eq!(matches.unmatched, [PathBuf::from("other.png")]);
+    }
+
+    #[tokio::test]
+    async fn test_write_image_captions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.png"), "a").unwrap();
+        std::fs::write(dir.path().join("a.png.txt"), "old").unwrap();
+        std::fs::write(dir.path().join("b.png"), "b").unwrap();
+        let dataset = Dataset::scan(dir.path()).await.unwrap();
+        let post = BooruPost {
+            tags: serde_json::from_value(json!({ "general": ["fur"] })).unwrap(),
+            ..BooruPost::default()
+        };
+        let images = vec![dir.path().join("a.png"), dir.path().join("b.png")];
+
+        let mut settings = settings(Config::default());
+        write_image_captions(&[(images, post)], &dataset, &mut settings).await.unwrap();
+        // The caption the dataset pairs with the image is replaced, not joined by another
+        let caption = std::fs::read_to_string(dir.path().join("b.txt")).unwrap();
+        assert!(caption.ends_with("fur"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.png.txt")).unwrap(), caption);
+        assert!(!dir.path().join("a.txt").exists());
+    }
+
+    #[test]
+    fn test_caption_styles() {
+        let post = BooruPost { score: 30, favorites: 300, ..BooruPost::default() };
+        let posts: Vec<(i64, i64)> = (0..20).map(|i| (i * 10, i * 100)).collect();
+
+        let plain = settings(Config::default());
+        assert!(!plain.uses_scores());
+        assert_eq!(plain.rating_tag(Rating::Explicit).as_deref(), Some("nsfw"));
+        assert_eq!(plain.rating_tag(Rating::Questionable).as_deref(), Some("questionable"));
+
+        let mut pony = settings(Config { caption_style: CaptionStyle::Pony, ..Config::default() });
+        assert!(pony.uses_scores());
+        pony.quality = Some(QualityBuckets::new(&posts));
+        assert_eq!(pony.rating_tag(Rating::Explicit).as_deref(), Some("rating_explicit"));
+        assert_eq!(pony.score_tag(&post).as_deref(), Some("score_5"));
+
+        let mut custom = settings(Config {
+            caption_style: CaptionStyle::Custom,
+            rating_template: Some("{rating} rating".to_string()),
+            ..Config::default()
+        });
+        assert!(!custom.uses_scores());
+        assert_eq!(custom.rating_tag(Rating::Safe).as_deref(), Some("safe rating"));
+        custom.quality = Some(QualityBuckets::new(&posts));
+        assert_eq!(custom.score_tag(&post), None);
+        custom.score_template = Some("quality {score}".to_string());
+        assert_eq!(custom.score_tag(&post).as_deref(), Some("quality 5"));
+    }
+
+    #[test]
+    fn test_sentences() {
+        let mut settings = settings(Config::default());
+        settings.sentences = vec![SentencePart::Characters, SentencePart::Pools];
+        let post = BooruPost {
+            rating: Rating::Safe,
+            tags: serde_json::from_value(json!({
+                "character": ["krystal_(star_fox)", "fox_mccloud", "falco_lombardi"],
+                "general": ["fur"],
+            })).unwrap(),
+            description: Some("[b]Commission[/b] for \"Alex\":https://example.com, thanks!".into()),
+            pools: vec!["Star Fox Comic".to_string()],
+            ..BooruPost::default()
+        };
+
+        let caption = post_caption(&post, &settings).unwrap();
+        assert_eq!(
+            caption.to_string(),
+            "safe, krystal (star fox), fox mccloud, falco lombardi, fur., \
+            Featuring Krystal (Star Fox), Fox Mccloud and Falco Lombardi. \
+            From the pool Star Fox Comic."
+        );
+        assert_eq!(Caption::parse(&caption.to_string()), caption);
+
+        // The description opens like a tag, so it follows the tags in the `tags., sentences` form
+        settings.sentences = vec![SentencePart::Description];
+        let caption = post_caption(&post, &settings).unwrap();
+        assert_eq!(caption.sentences, "Commission for Alex, thanks!");
+        assert!(caption.to_string().ends_with("fur., Commission for Alex, thanks!"));
+        assert_eq!(Caption::parse(&caption.to_string()), caption);
+        settings.description_length = 10;
+        assert_eq!(post_caption(&post, &settings).unwrap().sentences, "");
+    }
+}
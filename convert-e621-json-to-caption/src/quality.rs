@@ -0,0 +1,89 @@
+// convert-e621-json-to-caption\src\quality.rs
+
+// Quality tags
+//
+// Pony Diffusion and similar models are trained with `score_9` to `score_4` tags that rank the
+// images of a dataset by quality. They are derived from the `score` and `fav_count` of every
+// post, relative to the rest of the dataset: a first pass collects both for every post, and a
+// post's quality is its rank by score plus its rank by favorites. The best 10% of the posts
+// become `score_9`, the next 15% `score_8`, then 20% each for `score_7` to `score_5`, and the
+// lowest 15% `score_4`.
+
+/// The lowest quality percentile of `score_5` to `score_9`.
+const BUCKET_PERCENTILES: [usize; 5] = [15, 35, 55, 75, 90];
+
+/// The lowest quality score.
+const LOWEST_SCORE: usize = 4;
+
+/// Ranks posts by their score and favorites relative to a dataset.
+#[derive(Debug, Clone, Default)]
+pub struct QualityBuckets {
+    scores: Vec<i64>,
+    favorites: Vec<i64>,
+    /// The lowest quality of `score_5` to `score_9`.
+    thresholds: Vec<usize>,
+}
+
+impl QualityBuckets {
+    /// Computes the thresholds from the `(score, fav_count)` of every post of the dataset.
+    #[must_use = "Computes the thresholds and the result should be used"]
+    pub fn new(posts: &[(i64, i64)]) -> Self {
+        let mut scores: Vec<i64> = posts.iter().map(|&(score, _)| score).collect();
+        let mut favorites: Vec<i64> = posts.iter().map(|&(_, favorites)| favorites).collect();
+        scores.sort_unstable();
+        favorites.sort_unstable();
+        let mut buckets = Self { scores, favorites, thresholds: Vec::new() };
+
+        let mut qualities: Vec<usize> = posts
+            .iter()
+            .map(|&(score, favorites)| buckets.quality(score, favorites))
+            .collect();
+        qualities.sort_unstable();
+        if let Some(&best) = qualities.last() {
+            buckets.thresholds = BUCKET_PERCENTILES
+                .iter()
+                .map(|percentile| {
+                    qualities.get(qualities.len() * percentile / 100).copied().unwrap_or(best)
+                })
+                .collect();
+        }
+        buckets
+    }
+
+    /// Returns the number of posts the buckets were computed from.
+    #[must_use = "Returns the number of posts and the result should be used"]
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// Returns the number of posts below the given score, plus the number below the given
+    /// favorites.
+    fn quality(&self, score: i64, favorites: i64) -> usize {
+        self.scores.partition_point(|&other| other < score) +
+            self.favorites.partition_point(|&other| other < favorites)
+    }
+
+    /// Returns the quality score of a post, from 4 to 9.
+    #[must_use = "Returns the quality score and the result should be used"]
+    pub fn score(&self, score: i64, favorites: i64) -> usize {
+        let quality = self.quality(score, favorites);
+        LOWEST_SCORE + self.thresholds.iter().filter(|&&threshold| quality >= threshold).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        let posts: Vec<(i64, i64)> = (0..20).map(|i| (i * 10, i * 100)).collect();
+        let buckets = QualityBuckets::new(&posts);
+        let scores: Vec<usize> = posts.iter().map(|&(s, f)| buckets.score(s, f)).collect();
+        assert_eq!(scores, [4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9]);
+
+        // A post with many favorites but a low score lands in between
+        assert_eq!(buckets.score(0, 1900), 6);
+        assert_eq!(QualityBuckets::default().score(10, 10), 4);
+    }
+}
@@ -0,0 +1,153 @@
+// count-tokens\src\lib.rs
+
+// This program counts the CLIP tokens of every caption and lists the captions that do not fit
+// in the CLIP context, which trainers would silently cut. The limit defaults to 75 tokens, the
+// 77 of the context minus the start and end tokens.
+//
+// Tokens are counted offline with CLIP's byte-level BPE, loaded from a merges file: either
+// `bpe_simple_vocab_16e6.txt` from the CLIP repository, decompressed, or the `merges.txt` of a
+// Hugging Face CLIP tokenizer. A caption is counted the way trainers see it, with the keep
+// tokens joined to the tags instead of the `|||` separator.
+//
+// With `--truncate`, tags are dropped from the end of overlong captions until they fit. Tags
+// are written in order of importance by the converters, so the last ones go first. Keep tokens
+// and sentences are never dropped.
+//
+// Pass `--dry-run` to preview the truncation as a diff, or `--journal <dir>` to record it so
+// it can be reverted with `undo <dir>`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use anyhow::Result;
+use dataset_tools::{
+    read_file_content,
+    walk_directory_with,
+    write_to_file,
+    Caption,
+    ClipTokenizer,
+    CommonArgs,
+    CLIP_TOKEN_LIMIT,
+};
+use std::{ path::PathBuf, sync::Arc };
+use tokio::sync::Mutex;
+
+/// Count the CLIP tokens of captions and find or truncate the ones over the limit
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+
+    /// CLIP merges file, such as `bpe_simple_vocab_16e6.txt` or a tokenizer's `merges.txt`
+    #[arg(long, value_name = "FILE")]
+    pub merges: PathBuf,
+
+    /// Maximum number of tokens, without the start and end tokens
+    #[arg(short, long, value_name = "N", default_value_t = CLIP_TOKEN_LIMIT)]
+    pub limit: usize,
+
+    /// List the token count of every caption, not only the ones over the limit
+    #[arg(short, long)]
+    pub all: bool,
+
+    /// Drop tags from the end of overlong captions until they fit
+    #[arg(long)]
+    pub truncate: bool,
+}
+
+/// Returns the text of a caption as trainers tokenize it, with the keep tokens joined to the
+/// tags.
+#[must_use = "Returns the caption text and the result should be used"]
+pub fn prompt_text(caption: &Caption) -> String {
+    let mut prompt = Caption::default();
+    prompt.tags = caption.keep_tokens.iter().chain(&caption.tags).cloned().collect();
+    prompt.sentences.clone_from(&caption.sentences);
+    prompt.to_string()
+}
+
+/// Drops tags from the end of a caption until it fits in `limit` tokens. Keep tokens and
+/// sentences are never dropped, so the caption can still be over the limit afterwards.
+///
+/// # Returns
+///
+/// The dropped tags, in caption order.
+pub fn truncate(tokenizer: &ClipTokenizer, caption: &mut Caption, limit: usize) -> Vec<String> {
+    let mut dropped = Vec::new();
+    while tokenizer.count(&prompt_text(caption)) > limit {
+        let Some(tag) = caption.tags.pop() else {
+            break;
+        };
+        dropped.push(tag);
+    }
+    dropped.reverse();
+    dropped
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the merges file cannot be loaded, the directory cannot be walked or any
+/// caption fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    let config = common.config(&directory)?;
+    let tokenizer = Arc::new(ClipTokenizer::load(&args.merges)?);
+    let (limit, truncate_tags) = (args.limit, args.truncate);
+
+    let counts = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&counts);
+    let captions = config.caption_extension.as_str();
+    let summary = walk_directory_with(&directory, captions, &common.walk_options(), |path| {
+        let tokenizer = Arc::clone(&tokenizer);
+        let counts = Arc::clone(&collected);
+        async move {
+            let mut caption = Caption::parse(&read_file_content(&path).await?);
+            if truncate_tags {
+                let dropped = truncate(&tokenizer, &mut caption, limit);
+                if !dropped.is_empty() {
+                    println!("Truncated {}: dropped {}", path.display(), dropped.join(", "));
+                    write_to_file(&path, &caption.to_string()).await?;
+                }
+            }
+            let count = tokenizer.count(&prompt_text(&caption));
+            counts.lock().await.push((path, count));
+            Ok(())
+        }
+    }).await?;
+
+    let mut counts = std::mem::take(&mut *counts.lock().await);
+    counts.sort();
+    let mut over = 0;
+    for (path, count) in &counts {
+        if *count > limit {
+            over += 1;
+            println!("{}: {count} tokens ({} over)", path.display(), count - limit);
+        } else if args.all {
+            println!("{}: {count} tokens", path.display());
+        }
+    }
+    println!("{over} of {} captions exceed {limit} tokens.", counts.len());
+
+    summary.report()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        // Without merges, every character is a token
+        let tokenizer = ClipTokenizer::from_merges("#version: 0.2\n").unwrap();
+        let mut caption = Caption::parse("fox ||| ab, cd, ef, A fox sits.");
+        assert_eq!(prompt_text(&caption), "fox, ab, cd, ef, A fox sits.");
+        assert_eq!(tokenizer.count(&prompt_text(&caption)), 22);
+
+        assert_eq!(truncate(&tokenizer, &mut caption, 16), ["cd", "ef"]);
+        assert_eq!(caption.to_string(), "fox ||| ab, A fox sits.");
+        assert_eq!(truncate(&tokenizer, &mut caption, 1), ["ab"]);
+        assert_eq!(caption.to_string(), "fox ||| A fox sits.");
+    }
+}
@@ -0,0 +1,26 @@
+// count-tokens\src\main.rs
+
+// Standalone `count-tokens` binary, the same tool is available as `dataset-tools count-tokens`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use clap::Parser;
+use dataset_tools::CommonArgs;
+
+#[derive(Parser)]
+#[command(name = "count-tokens", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: count_tokens::Args,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    count_tokens::run(cli.args, &cli.common).await
+}
@@ -0,0 +1,58 @@
+// create-empty-caption-files\src\lib.rs
+
+// This program creates empty caption files for all image files in a directory.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use dataset_tools::{ walk_paths, write_to_file, CommonArgs, Dataset, SidecarKind };
+use std::path::{ Path, PathBuf };
+use anyhow::{ bail, Result };
+
+/// Create an empty caption file next to every image that has none
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for images
+    pub directory: Option<PathBuf>,
+
+    /// Extension of the caption files, overrides `caption-extension` from the config
+    #[arg(long)]
+    pub ext: Option<String>,
+}
+
+async fn create_caption_file(caption_file: PathBuf) -> Result<()> {
+    write_to_file(&caption_file, "").await?;
+    println!("Created caption file: {}", caption_file.display());
+    Ok(())
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any caption file cannot be created.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    let extension = match args.ext {
+        Some(ext) => ext,
+        None => common.config(&directory)?.caption_extension,
+    };
+
+    // Images only count as captioned by the caption files the dataset pairs them with
+    if SidecarKind::from_path(&Path::new("caption").with_extension(&extension)) !=
+        Some(SidecarKind::Caption)
+    {
+        bail!("The extension .{extension} is not a caption extension.");
+    }
+
+    println!("Processing directory: {}", directory.display());
+    println!("Using extension: .{extension}");
+
+    let dataset = Dataset::scan_with(&directory, &common.walk_options()).await?;
+    let caption_files = dataset.uncaptioned().map(|sample| sample.image.with_extension(&extension));
+    walk_paths(caption_files, &common.walk_options(), create_caption_file).await.report()?;
+
+    println!("All caption files have been created.");
+    Ok(())
+}
@@ -0,0 +1,154 @@
+// dataset-tools-cli\src\main.rs
+
+// A single `dataset-tools` binary exposing every tool as a subcommand, for example
+// `dataset-tools rplc foo bar --dir ./dataset`. The flags shared by all tools (`--dir`,
+// `--dry-run`, `--jobs`, `--verbose`, ...) can be given before or after the subcommand.
+//
+// Like busybox, the binary also looks at the name it was started as: a copy or symlink named
+// after a tool, such as `rplc` or `rplc.exe`, behaves like `dataset-tools rplc`, so scripts
+// written for the old standalone binaries keep working. Old package names are accepted as
+// aliases too.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::{ env, ffi::OsString, path::Path, process::ExitCode };
+use clap::{ CommandFactory, Parser, Subcommand };
+use dataset_tools::CommonArgs;
+
+const BIN_NAME: &str = "dataset-tools";
+
+#[derive(Parser)]
+#[command(name = BIN_NAME, version, about = "Tools for working with training datasets")]
+struct Cli {
+    #[command(subcommand)]
+    tool: Tool,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Subcommand)]
+enum Tool {
+    CaptionDiff(caption_diff::Args),
+    Check(check::Args),
+    CompressExe(compress_exe::Args),
+    ConvertCaptionJsonToTxt(convert_caption_json_to_txt::Args),
+    ConvertE621JsonToCaption(Box<convert_e621_json_to_caption::Args>),
+    CountTokens(count_tokens::Args),
+    CreateEmptyCaptionFiles(create_empty_caption_files::Args),
+    EditTags(edit_tags::Args),
+    ExtractMetadata(extract_metadata::Args),
+    FilterTags(filter_tags::Args),
+    FixMultilineTags(fix_multiline_tags::Args),
+    FormatJson(format_json::Args),
+    InsertPedantic(insert_pedantic::Args),
+    KeepTokens(keep_tokens::Args),
+    ListLoraBlocks(list_lora_blocks::Args),
+    NormalizeEncoding(normalize_encoding::Args),
+    #[command(alias = "remove-escaper-characters")]
+    RemoveEscapeCharacters(remove_escape_characters::Args),
+    RemoveExtraFileExtensions(remove_extra_file_extensions::Args),
+    RemoveMacArtifacts(remove_mac_artifacts::Args),
+    RemoveTransparency(remove_transparency::Args),
+    RemoveUrlFiles(remove_url_files::Args),
+    RenameToMd5(rename_to_md5::Args),
+    ResolveTags(resolve_tags::Args),
+    Rplc(rplc::Args),
+    SearchForSuperscriptNumbers(search_for_superscript_numbers::Args),
+    #[command(name = "simpletuner2kohya")]
+    Simpletuner2kohya(simpletuner2kohya::Args),
+    TagStats(tag_stats::Args),
+    Undo(undo::Args),
+}
+
+/// Returns the command line arguments, with the tool inserted as the subcommand if the binary
+/// was started under the name of a tool.
+fn args_with_tool_name() -> Vec<OsString> {
+    let mut args: Vec<OsString> = env::args_os().collect();
+    let tool = args
+        .first()
+        .and_then(|arg| Path::new(arg).file_stem())
+        .and_then(|stem| stem.to_str())
+        .map(str::to_owned);
+
+    if let Some(tool) = tool {
+        if tool != BIN_NAME && Cli::command().find_subcommand(&tool).is_some() {
+            args[0] = BIN_NAME.into();
+            args.insert(1, tool.into());
+        }
+    }
+    args
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse_from(args_with_tool_name());
+    let common = &cli.common;
+
+    let result = match cli.tool {
+        Tool::CaptionDiff(args) => caption_diff::run(args, common).await,
+        Tool::Check(args) => {
+            return check::run_with_exit_code(args, common).await;
+        }
+        Tool::CompressExe(args) => compress_exe::run(args, common).await,
+        Tool::ConvertCaptionJsonToTxt(args) => convert_caption_json_to_txt::run(args, common).await,
+        Tool::ConvertE621JsonToCaption(args) => {
+            convert_e621_json_to_caption::run(*args, common).await
+        }
+        Tool::CountTokens(args) => count_tokens::run(args, common).await,
+        Tool::CreateEmptyCaptionFiles(args) => create_empty_caption_files::run(args, common).await,
+        Tool::EditTags(args) => edit_tags::run(args, common).await,
+        Tool::ExtractMetadata(args) => extract_metadata::run(args, common).await,
+        Tool::FilterTags(args) => filter_tags::run(args, common).await,
+        Tool::FixMultilineTags(args) => fix_multiline_tags::run(args, common).await,
+        Tool::FormatJson(args) => format_json::run(args, common).await,
+        Tool::InsertPedantic(args) => insert_pedantic::run(args, common).await,
+        Tool::KeepTokens(args) => keep_tokens::run(args, common).await,
+        Tool::ListLoraBlocks(args) => list_lora_blocks::run(args, common),
+        Tool::NormalizeEncoding(args) => normalize_encoding::run(args, common).await,
+        Tool::RemoveEscapeCharacters(args) => remove_escape_characters::run(args, common).await,
+        Tool::RemoveExtraFileExtensions(args) => {
+            remove_extra_file_extensions::run(args, common).await
+        }
+        Tool::RemoveMacArtifacts(args) => remove_mac_artifacts::run(args, common).await,
+        Tool::RemoveTransparency(args) => remove_transparency::run(args, common).await,
+        Tool::RemoveUrlFiles(args) => remove_url_files::run(args, common).await,
+        Tool::RenameToMd5(args) => rename_to_md5::run(args, common).await,
+        Tool::ResolveTags(args) => resolve_tags::run(args, common).await,
+        Tool::Rplc(args) => rplc::run(args, common).await,
+        Tool::SearchForSuperscriptNumbers(args) => {
+            search_for_superscript_numbers::run(args, common).await
+        }
+        Tool::Simpletuner2kohya(args) => simpletuner2kohya::run(args, common).await,
+        Tool::TagStats(args) => tag_stats::run(args, common).await,
+        Tool::Undo(args) => undo::run(args, common).await,
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_tool_names() {
+        let command = Cli::command();
+        for name in ["rplc", "remove-escaper-characters", "rename-to-md5", "simpletuner2kohya"] {
+            assert!(command.find_subcommand(name).is_some(), "{name}");
+        }
+        assert!(command.find_subcommand(BIN_NAME).is_none());
+    }
+}
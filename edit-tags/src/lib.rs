@@ -0,0 +1,82 @@
+// edit-tags\src\lib.rs
+
+// This program edits the tags of existing captions as whole tags, unlike `rplc`, which
+// replaces substrings and so also rewrites `catgirl` when replacing `cat`.
+//
+// Rules add, remove, rename or move tags, and can depend on the tags a caption has or lacks:
+//
+// ```text
+// add feral if solo, wolf, not anthro
+// remove canine if wolf
+// rename grey wolf => gray wolf
+// move solo => first
+// ```
+//
+// Rules come from a file given with `--file`, one per line, followed by the rules given with
+// `--rule`, and are applied in that order. Every changed caption is listed with its changes.
+//
+// Pass `--dry-run` to preview the changes as a diff, or `--journal <dir>` to record them so
+// they can be reverted with `undo <dir>`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+pub mod rules;
+
+use anyhow::Result;
+use dataset_tools::{ read_file_content, walk_directory_with, write_to_file, Caption, CommonArgs };
+use std::{ path::PathBuf, sync::Arc };
+
+use rules::{ load_rules, Rule };
+
+/// Add, remove, rename and move whole tags in captions, optionally depending on other tags
+#[derive(clap::Args, Debug)]
+#[command(group = clap::ArgGroup::new("sources").required(true).multiple(true))]
+pub struct Args {
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+
+    /// Rule to apply, such as `add feral if solo, wolf`, can be repeated
+    #[arg(short = 'e', long = "rule", value_name = "RULE", group = "sources")]
+    pub rules: Vec<Rule>,
+
+    /// File of rules to apply, one per line, before the rules given with `--rule`
+    #[arg(short, long, value_name = "FILE", group = "sources")]
+    pub file: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if a rule is invalid, the directory cannot be walked or any caption fails
+/// to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    let config = common.config(&directory)?;
+
+    let mut rules = match &args.file {
+        Some(path) => load_rules(path)?,
+        None => Vec::new(),
+    };
+    rules.extend(args.rules);
+    let rules = Arc::new(rules);
+
+    let captions = config.caption_extension.as_str();
+    walk_directory_with(&directory, captions, &common.walk_options(), |path| {
+        let rules = Arc::clone(&rules);
+        async move {
+            let mut caption = Caption::parse(&read_file_content(&path).await?);
+            let mut changes = Vec::new();
+            for rule in rules.iter() {
+                rule.apply(&mut caption, &mut changes);
+            }
+            if !changes.is_empty() {
+                println!("{}: {}", path.display(), changes.join(", "));
+                write_to_file(&path, &caption.to_string()).await?;
+            }
+            Ok(())
+        }
+    }).await?.report()
+}
@@ -0,0 +1,345 @@
+// edit-tags\src\rules.rs
+
+// Tag edit rules
+//
+// A rule is an action on the tags of a caption, optionally followed by `if` and the tags the
+// caption must have for the action to apply. A condition prefixed with `not` requires the tag
+// to be missing instead.
+//
+// ```text
+// add feral, quadruped if solo, wolf, not anthro
+// remove canine if wolf
+// rename grey wolf => gray wolf
+// move solo => first
+// ```
+//
+// `add` and `remove` take a list of tags, split like the tags of a caption so `hi\, there` is
+// one tag, and `move` takes `first`, `last` or a 1-based position.
+// Tags are compared as whole tags in their e621 form, so `cat` never matches `catgirl` and
+// `grey wolf` also matches `grey_wolf`. Added and renamed tags are written the way the caption
+// writes its other tags. Conditions see the keep tokens too, but actions only change the tags.
+
+use std::{ fmt, path::Path, str::FromStr };
+use anyhow::{ bail, Context, Result };
+use dataset_tools::{ decode_text, e621::e621_name, split_tags, Caption };
+
+/// Where `move` puts a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    First,
+    Last,
+    /// A 0-based index, clamped to the tags of the caption.
+    Index(usize),
+}
+
+impl FromStr for Position {
+    type Err = anyhow::Error;
+
+    fn from_str(position: &str) -> Result<Self> {
+        match position {
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            _ => match position.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Self::Index(n - 1)),
+                _ => bail!("Invalid position `{position}`, expected first, last or a number"),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::First => f.write_str("first"),
+            Self::Last => f.write_str("last"),
+            Self::Index(i) => write!(f, "{}", i + 1),
+        }
+    }
+}
+
+/// What a rule does to the tags of a caption. Tags are held as e621 names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Appends the tags the caption does not have yet.
+    Add(Vec<String>),
+    /// Removes every occurrence of the tags.
+    Remove(Vec<String>),
+    /// Renames a tag in place, or removes it if the caption already has the new tag.
+    Rename(String, String),
+    /// Moves a tag to another position.
+    Move(String, Position),
+}
+
+/// A tag that a caption must have, or must not have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Condition {
+    name: String,
+    present: bool,
+}
+
+/// An action and the conditions under which it applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub action: Action,
+    conditions: Vec<Condition>,
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(rule: &str) -> Result<Self> {
+        let (action, conditions) = match rule.split_once(" if ") {
+            Some((action, conditions)) => (action.trim(), parse_conditions(conditions)?),
+            None => (rule.trim(), Vec::new()),
+        };
+        let (keyword, arguments) = action.split_once(' ').unwrap_or((action, ""));
+
+        let action = match keyword {
+            "add" => Action::Add(parse_tags(arguments)?),
+            "remove" => Action::Remove(parse_tags(arguments)?),
+            "rename" => {
+                let (old, new) = split_arrow(arguments)?;
+                Action::Rename(parse_tag(old)?, parse_tag(new)?)
+            }
+            "move" => {
+                let (tag, position) = split_arrow(arguments)?;
+                Action::Move(parse_tag(tag)?, position.trim().parse()?)
+            }
+            _ => bail!("Unknown action `{keyword}`, expected add, remove, rename or move"),
+        };
+        Ok(Self { action, conditions })
+    }
+}
+
+impl Rule {
+    /// Returns `true` if the caption meets the conditions of the rule.
+    #[must_use = "Determines if the rule applies and the result should be checked"]
+    pub fn matches(&self, caption: &Caption) -> bool {
+        self.conditions.iter().all(|condition| has(caption, &condition.name) == condition.present)
+    }
+
+    /// Applies the rule to a caption that meets its conditions, adding a short description of
+    /// every change to `changes`.
+    pub fn apply(&self, caption: &mut Caption, changes: &mut Vec<String>) {
+        if !self.matches(caption) {
+            return;
+        }
+        match &self.action {
+            Action::Add(names) => {
+                for name in names {
+                    if !has(caption, name) {
+                        let tag = caption.styled_tag(name);
+                        changes.push(format!("+{tag}"));
+                        caption.tags.push(tag);
+                    }
+                }
+            }
+            Action::Remove(names) => {
+                for name in names {
+                    caption.tags.retain(|tag| {
+                        let keep = e621_name(tag) != *name;
+                        if !keep {
+                            changes.push(format!("-{tag}"));
+                        }
+                        keep
+                    });
+                }
+            }
+            Action::Rename(old, new) => {
+                if old == new {
+                    return;
+                }
+                // Later occurrences are removed, as the caption has the new tag by then
+                while let Some(i) = position(&caption.tags, old) {
+                    if has(caption, new) {
+                        changes.push(format!("-{}", caption.tags.remove(i)));
+                    } else {
+                        let tag = caption.styled_tag(new);
+                        changes.push(format!("{} => {tag}", caption.tags[i]));
+                        caption.tags[i] = tag;
+                    }
+                }
+            }
+            Action::Move(name, to) => {
+                let Some(i) = position(&caption.tags, name) else {
+                    return;
+                };
+                let last = caption.tags.len() - 1;
+                let target = match to {
+                    Position::First => 0,
+                    Position::Last => last,
+                    Position::Index(index) => (*index).min(last),
+                };
+                if target != i {
+                    let tag = caption.tags.remove(i);
+                    changes.push(format!("moved {tag} to {to}"));
+                    caption.tags.insert(target, tag);
+                }
+            }
+        }
+    }
+}
+
+/// Parses the lines of a rule file, skipping blank lines and lines starting with `#`.
+///
+/// # Errors
+///
+/// Returns an error naming the line of the first invalid rule.
+pub fn parse_rules<S: AsRef<str>>(lines: &[S]) -> Result<Vec<Rule>> {
+    let mut rules = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let line = line.as_ref().trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        rules.push(line.parse().with_context(|| format!("Invalid rule on line {}", i + 1))?);
+    }
+    Ok(rules)
+}
+
+/// Reads a rule file in any common encoding.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or holds an invalid rule.
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>> {
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let content = decode_text(&bytes).text;
+    let lines: Vec<&str> = content.lines().collect();
+    parse_rules(&lines).with_context(|| format!("Invalid rule file {}", path.display()))
+}
+
+fn parse_tag(tag: &str) -> Result<String> {
+    let name = e621_name(tag);
+    if name.is_empty() {
+        bail!("Missing tag");
+    }
+    Ok(name)
+}
+
+/// Splits a list of tags the way captions are split, so `\,` escapes a comma.
+fn parse_tags(tags: &str) -> Result<Vec<String>> {
+    let tags = split_tags(tags);
+    if tags.is_empty() {
+        bail!("Missing tag");
+    }
+    tags.iter().map(|tag| parse_tag(tag)).collect()
+}
+
+fn parse_conditions(conditions: &str) -> Result<Vec<Condition>> {
+    let conditions = split_tags(conditions);
+    if conditions.is_empty() {
+        bail!("Missing tag");
+    }
+    conditions
+        .iter()
+        .map(|condition| {
+            match condition.strip_prefix("not ") {
+                Some(tag) => Ok(Condition { name: parse_tag(tag)?, present: false }),
+                None => Ok(Condition { name: parse_tag(condition)?, present: true }),
+            }
+        })
+        .collect()
+}
+
+fn split_arrow(arguments: &str) -> Result<(&str, &str)> {
+    arguments.split_once("=>").context("Expected `<tag> => <target>`")
+}
+
+/// Returns the index of the first tag with the given e621 name.
+fn position(tags: &[String], name: &str) -> Option<usize> {
+    tags.iter().position(|tag| e621_name(tag) == name)
+}
+
+/// Returns `true` if the keep tokens or tags of a caption include the given e621 name.
+fn has(caption: &Caption, name: &str) -> bool {
+    caption.keep_tokens.iter().chain(&caption.tags).any(|tag| e621_name(tag) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(caption: &str, rules: &[&str]) -> (String, Vec<String>) {
+        let mut caption = Caption::parse(caption);
+        let mut changes = Vec::new();
+        for rule in parse_rules(rules).unwrap() {
+            rule.apply(&mut caption, &mut changes);
+        }
+        (caption.to_string(), changes)
+    }
+
+    #[test]
+    fn test_parse() {
+        let rule: Rule = "add feral, Quadruped if solo, not anthro".parse().unwrap();
+        assert_eq!(rule.action, Action::Add(vec!["feral".into(), "quadruped".into()]));
+        assert_eq!(rule.conditions[1], Condition { name: "anthro".into(), present: false });
+
+        let rule: Rule = "move wolf \\(character\\) => 2".parse().unwrap();
+        assert_eq!(rule.action, Action::Move("wolf_(character)".into(), Position::Index(1)));
+
+        for invalid in ["paint wolf", "add", "rename wolf", "move wolf => 0"] {
+            assert!(invalid.parse::<Rule>().is_err(), "{invalid}");
+        }
+        assert!(parse_rules(&["# Comment", "", "remove text"]).is_ok());
+    }
+
+    #[test]
+    fn test_escaped_comma() {
+        let rule: Rule = r"remove hi\, there, solo if not hi\, you".parse().unwrap();
+        assert_eq!(rule.action, Action::Remove(vec!["hi,_there".into(), "solo".into()]));
+        assert_eq!(rule.conditions, [Condition { name: "hi,_you".into(), present: false }]);
+
+        let (caption, changes) = edit(r"wolf, hi\, there, solo", &[r"remove hi\, there"]);
+        assert_eq!(caption, "wolf, solo");
+        assert_eq!(changes, [r"-hi\, there"]);
+
+        let (caption, _) = edit("wolf", &[r"add hi\, there if wolf"]);
+        assert_eq!(caption, r"wolf, hi\, there");
+    }
+
+    #[test]
+    fn test_load_rules_with_bom() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("rules.txt");
+        std::fs::write(&path, "\u{feff}remove canine\r\nadd feral\r\n").unwrap();
+        let rules = load_rules(&path).unwrap();
+        assert_eq!(rules[0].action, Action::Remove(vec!["canine".into()]));
+    }
+
+    #[test]
+    fn test_conditions() {
+        let rules = ["add feral if solo, wolf, not anthro", "remove canine if wolf"];
+        let (caption, changes) = edit("solo, wolf, canine, bobcat, A wolf howls.", &rules);
+        assert_eq!(caption, "solo, wolf, bobcat, feral, A wolf howls.");
+        assert_eq!(changes, ["+feral", "-canine"]);
+
+        let (caption, changes) = edit("anthro ||| solo, wolf, canine", &rules);
+        assert_eq!(caption, "anthro ||| solo, wolf");
+        assert_eq!(changes, ["-canine"]);
+
+        // Whole tags only
+        let (caption, changes) = edit("catgirl, bobcat", &["remove cat", "add cat if cat"]);
+        assert_eq!(caption, "catgirl, bobcat");
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn test_rename_and_move() {
+        let (caption, changes) = edit(
+            r"solo, grey wolf, wolf \(character\)",
+            &["rename grey_wolf => gray wolf \\(species\\)", "move solo => last"]
+        );
+        assert_eq!(caption, r"gray wolf \(species\), wolf \(character\), solo");
+        assert_eq!(changes, [r"grey wolf => gray wolf \(species\)", "moved solo to last"]);
+
+        let rules = ["rename grey wolf => gray wolf"];
+        let (caption, changes) = edit("grey_wolf, solo, gray_wolf", &rules);
+        assert_eq!(caption, "solo, gray_wolf");
+        // The new tag is already there, so the old one is only removed
+        assert_eq!(changes, ["-grey_wolf"]);
+
+        let (caption, _) = edit("a, b, c", &["move c => 2", "move a => 9"]);
+        assert_eq!(caption, "c, b, a");
+    }
+}
@@ -0,0 +1,52 @@
+// extract-metadata\src\lib.rs
+
+// This program extracts metadata from .safetensors files in a target directory and subdirectories.
+// The target can also be a single file or a glob pattern.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use dataset_tools::{ walk_directory_with, process_safetensors_file, CommonArgs };
+use std::path::PathBuf;
+use glob::glob;
+use anyhow::Context;
+
+/// Print the metadata of .safetensors files
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// File, directory or glob pattern of .safetensors files
+    pub path: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the path is invalid or any file's metadata cannot be read.
+pub async fn run(args: Args, common: &CommonArgs) -> anyhow::Result<()> {
+    common.init()?;
+    let path = common.dir_or(args.path);
+
+    if path.is_dir() {
+        walk_directory_with(&path, "safetensors", &common.walk_options(), |file_path| {
+            async move { process_safetensors_file(&file_path).await }
+        }).await?.report()?;
+    } else if let Some(path_str) = path.to_str() {
+        if path_str.contains('*') {
+            for entry in glob(path_str).context("Failed to read glob pattern")? {
+                match entry {
+                    Ok(path) => {
+                        process_safetensors_file(&path).await?;
+                    }
+                    Err(e) => println!("Error processing entry: {e:?}"),
+                }
+            }
+        } else {
+            process_safetensors_file(&path).await?;
+        }
+    } else {
+        return Err(anyhow::anyhow!("Invalid path provided"));
+    }
+
+    Ok(())
+}
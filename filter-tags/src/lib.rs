@@ -0,0 +1,63 @@
+// filter-tags\src\lib.rs
+
+// This program removes tags from existing captions with the same filter the converters use:
+// the `ignored-tags` regexes of the nearest `dataset-tools.toml` and its `tag-rules` file, or
+// the rule file given with `--rules <file>`.
+//
+// A rule file lists literal tags, globs such as `*_(artist)` and regexes prefixed with `re:`,
+// one per line. With `--rules-mode whitelist` only the tags matching a rule are kept. Keep
+// tokens and sentences are left alone.
+//
+// Pass `--dry-run` to preview the changes as a diff, or `--journal <dir>` to record them so
+// they can be reverted with `undo <dir>`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use anyhow::Result;
+use dataset_tools::{
+    read_file_content,
+    walk_directory_with,
+    write_to_file,
+    Caption,
+    CommonArgs,
+    FilterArgs,
+    TagFilter,
+};
+use std::{ path::PathBuf, sync::Arc };
+
+/// Remove tags from captions with a rule file of literal tags, globs and regexes
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the rules are invalid, the directory cannot be walked or any caption
+/// fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    let config = common.config(&directory)?;
+    let filter = Arc::new(TagFilter::from_config(&config, &args.filter)?);
+
+    let captions = config.caption_extension.as_str();
+    walk_directory_with(&directory, captions, &common.walk_options(), |path| {
+        let filter = Arc::clone(&filter);
+        async move {
+            let mut caption = Caption::parse(&read_file_content(&path).await?);
+            if filter.apply(&mut caption) {
+                println!("Filtered tags: {}", path.display());
+                write_to_file(&path, &caption.to_string()).await?;
+            }
+            Ok(())
+        }
+    }).await?.report()
+}
@@ -0,0 +1,26 @@
+// filter-tags\src\main.rs
+
+// Standalone `filter-tags` binary, the same tool is available as `dataset-tools filter-tags`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use clap::Parser;
+use dataset_tools::CommonArgs;
+
+#[derive(Parser)]
+#[command(name = "filter-tags", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: filter_tags::Args,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    filter_tags::run(cli.args, &cli.common).await
+}
@@ -0,0 +1,51 @@
+// fix-multiline-tags\src\lib.rs
+
+// This program joins captions whose tags are spread over several lines into a single
+// comma-separated line.
+
+use std::path::PathBuf;
+use anyhow::Result;
+use dataset_tools::{ walk_directory_with, read_file_content, write_to_file, Caption, CommonArgs };
+
+/// Join tags spread over several lines into a single line
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any file fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    let config = common.config(&directory)?;
+
+    // Word lists and sample prompts are skipped by the walk's default excludes
+    let captions = config.caption_extension.as_str();
+    walk_directory_with(&directory, captions, &common.walk_options(), |path| async move {
+        // Read file content
+        let content = read_file_content(&path).await?;
+        
+        // Process the content
+        let fixed_content = fix_tags(&content);
+        
+        // Write back to file
+        write_to_file(&path, &fixed_content).await?;
+        
+        println!("Processed: {}", path.display());
+        Ok(())
+    })
+    .await?
+    .report()
+}
+
+fn fix_tags(content: &str) -> String {
+    // Line breaks separate tags like commas do, and the canonical form joins them
+    let mut caption = Caption::parse(content);
+    caption.normalize();
+    caption.to_string()
+}
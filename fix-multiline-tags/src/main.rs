@@ -1,49 +1,26 @@
-use std::path::Path;
-use anyhow::Result;
-use dataset_tools::{walk_directory, read_file_content, write_to_file};
+// fix-multiline-tags\src\main.rs
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Get directory from args or use current directory
-    let directory = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| ".".to_string());
+// Standalone `fix-multiline-tags` binary, the same tool is available as `dataset-tools fix-multiline-tags`.
 
-    walk_directory(Path::new(&directory), "txt", |path| async move {
-        // Skip specific files
-        if let Some(file_name) = path.file_name().and_then(|f| f.to_str()) {
-            if file_name.contains("wordfreq.txt") || file_name.contains("sample-prompts.txt") {
-                return Ok(());
-            }
-        }
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
 
-        // Read file content
-        let content = read_file_content(path.to_str().unwrap()).await?;
-        
-        // Process the content
-        let fixed_content = fix_tags(&content);
-        
-        // Write back to file
-        write_to_file(&path, &fixed_content).await?;
-        
-        println!("Processed: {}", path.display());
-        Ok(())
-    })
-    .await?;
+use clap::Parser;
+use dataset_tools::CommonArgs;
 
-    Ok(())
-}
+#[derive(Parser)]
+#[command(name = "fix-multiline-tags", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
 
-fn fix_tags(content: &str) -> String {
-    // Split content into lines and process each line
-    let tags: Vec<String> = content
-        .lines()
-        .flat_map(|line| line.split(','))
-        .map(str::trim)
-        .filter(|s| !s.is_empty())
-        .map(String::from)
-        .collect();
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: fix_multiline_tags::Args,
+}
 
-    // Join all tags with comma and space
-    tags.join(", ")
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    fix_multiline_tags::run(cli.args, &cli.common).await
 }
@@ -0,0 +1,62 @@
+// format-json\src\lib.rs
+
+// This script is used to format JSON files in a directory and its subdirectories.
+// It takes an optional command line argument which is the path to the directory.
+// If no argument is provided, it uses `--dir` or the current directory.
+// It uses the `serde_json` crate to parse and format the JSON files,
+// and the shared directory walk to recursively traverse directories.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use dataset_tools::{ walk_directory_with, format_json_file, CommonArgs };
+use std::path::PathBuf;
+use anyhow::Result;
+
+/// Pretty-print every JSON file in a directory
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for JSON files
+    pub directory: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any JSON file fails to format.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    walk_directory_with(&directory, "json", &common.walk_options(), format_json_file)
+        .await?
+        .report()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_format_json() {
+        let temp_dir = TempDir::new().unwrap();
+        
+        // Create unformatted JSON file
+        let unformatted = r#"{"a":1,"b": 2,"c":    3}"#;
+        let expected = r#"{
+  "a": 1,
+  "b": 2,
+  "c": 3
+}"#;
+
+        let file_path = temp_dir.path().join("test.json");
+        fs::write(&file_path, unformatted).unwrap();
+
+        format_json_file(file_path.clone()).await.unwrap();
+
+        let formatted = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(formatted.trim(), expected);
+    }
+}
@@ -0,0 +1,73 @@
+// insert-pedantic\src\lib.rs
+
+// This program inserts a pedantic warning comment in all .rs files in a target directory
+// and subdirectories.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::path::{ PathBuf, Path };
+use dataset_tools::{ process_rust_file, walk_rust_files_with, write_to_file, CommonArgs, WalkOptions };
+use anyhow::{ Result, Context, anyhow };
+use tokio::{ fs, io };
+
+/// Insert the pedantic clippy warning into Rust files that lack it
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Rust file or directory of Rust files
+    pub target: Option<PathBuf>,
+}
+
+const WARNING_COMMENT: &str =
+    r"// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+";
+
+async fn insert_warning(path: &Path, files_without_warning: &mut Vec<PathBuf>) -> Result<()> {
+    if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+        return Err(anyhow!("File is not a Rust source file: {}", path.display()));
+    }
+    process_rust_file(path, files_without_warning).await.map_err(io::Error::other)?;
+    if files_without_warning.contains(&path.to_path_buf()) {
+        let content = fs::read_to_string(path).await.context("Failed to read file")?;
+        let new_content = format!("{WARNING_COMMENT}{content}");
+        write_to_file(path, &new_content).await.context("Failed to write to file")?;
+        println!("Inserted warning in: {}", path.display());
+    } else {
+        println!("Warning already present in: {}", path.display());
+    }
+    Ok(())
+}
+
+async fn process_files(target: &Path, walk_options: &WalkOptions) -> io::Result<()> {
+    let mut files_without_warning = Vec::new();
+    if target.is_file() {
+        insert_warning(target, &mut files_without_warning).await.map_err(io::Error::other)?;
+        println!("Processed file: {}", target.display());
+    } else if target.is_dir() {
+        walk_rust_files_with(target, walk_options, |path| {
+            {
+                let mut value = files_without_warning.clone();
+                async move {
+                    insert_warning(&path, &mut value).await.map_err(io::Error::other)
+                }
+            }
+        }).await?;
+    } else {
+        println!("Invalid path: {}", target.display());
+    }
+    Ok(())
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the target cannot be walked or any file fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let target = common.dir_or(args.target);
+    process_files(&target, &common.walk_options()).await?;
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+// keep-tokens\src\lib.rs
+
+// This code is designed to process a directory of .txt files.
+//
+// It takes a list of "keep tokens" that should be retained in the files, from `--keep` or
+// the `keep-tokens` of the nearest `dataset-tools.toml`, `feral, weasel` by default.
+// For each .txt file found, it parses the caption into tags and sentences, taking the tags
+// of an earlier `|||` prefix back in so running the tool twice changes nothing.
+//
+// Finally, it writes a new version of the file with the
+// format: `keep_tokens ||| filtered_tags, sentences`.
+//
+// The directory can be given as the first argument or with `--dir`. Pass `--dry-run` to preview the
+// changes as a diff, or `--journal <dir>` to record them so they can be reverted with
+// `undo <dir>`. `--backup` keeps the originals as `.bak` files.
+//
+// The code uses the shared directory walk to recursively traverse the directory and find
+// the .txt files, which simplifies the code compared to using the standard library's
+// `read_dir` function.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use dataset_tools::{
+    walk_directory_with,
+    read_file_content,
+    unescape_tag,
+    write_to_file,
+    journal,
+    Caption,
+    CommonArgs,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::Result;
+
+/// Move the keep tokens to the front of every caption
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for .txt files
+    pub directory: Option<PathBuf>,
+
+    /// Tokens to keep at the front of the caption, overrides `keep-tokens` from the config
+    #[arg(short, long = "keep", value_delimiter = ',')]
+    pub keep_tokens: Option<Vec<String>>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any file fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    let config = common.config(&directory)?;
+    let keep_tokens = Arc::new(args.keep_tokens.unwrap_or(config.keep_tokens));
+
+    println!(
+        "Searching for .{} files in directory: {}",
+        config.caption_extension,
+        directory.display()
+    );
+
+    // Sample prompt files are skipped by the walk's default excludes
+    let captions = config.caption_extension.as_str();
+    walk_directory_with(&directory, captions, &common.walk_options(), |path| {
+        let keep_tokens = Arc::clone(&keep_tokens);
+        async move {
+            println!("Processing file: {}", path.display());
+            let content = read_file_content(&path).await?;
+            let new_content = keep_tokens_in(&content, &keep_tokens);
+
+            write_to_file(&path, &new_content).await?;
+            if !journal::is_dry_run() {
+                println!("Wrote new content to file: {}", path.display());
+            }
+            Ok(())
+        }
+    }).await?.report()
+}
+
+/// Moves the keep tokens to the front of a caption, before the `|||` separator.
+///
+/// Tags before an existing separator go back to the tags, so the caption only ever has the
+/// given keep tokens in front and every other tag once.
+#[must_use = "Returns the new caption and the result should be used"]
+pub fn keep_tokens_in(content: &str, keep_tokens: &[String]) -> String {
+    let mut caption = Caption::parse(content);
+    let mut tags = std::mem::take(&mut caption.keep_tokens);
+    tags.append(&mut caption.tags);
+
+    caption.tags = tags
+        .into_iter()
+        .filter(|tag| !keep_tokens.iter().any(|keep| unescape_tag(keep) == unescape_tag(tag)))
+        .collect();
+    caption.keep_tokens = keep_tokens.to_vec();
+    caption.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_keep_tokens() {
+        let temp_dir = TempDir::new().unwrap();
+        let keep_tokens = ["feral".to_string(), "weasel".to_string()];
+        
+        // Create test caption file
+        let original = "feral, cat, dog, weasel, running\nThis is a description";
+        let expected = "feral, weasel ||| cat, dog, running\nThis is a description";
+
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, original).unwrap();
+
+        // Process the file
+        let content = read_file_content(file_path.to_str().unwrap()).await.unwrap();
+        let new_content = keep_tokens_in(&content, &keep_tokens);
+
+        assert_eq!(new_content, expected);
+        // Running again changes nothing
+        assert_eq!(keep_tokens_in(&new_content, &keep_tokens), expected);
+    }
+}
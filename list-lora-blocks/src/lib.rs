@@ -0,0 +1,51 @@
+// list-lora-blocks\src\lib.rs
+
+// This program lists the tensors stored in a .safetensors file, optionally with their shape,
+// data type and size.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use safetensors::SafeTensors;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use anyhow::{ Context, Result };
+use dataset_tools::CommonArgs;
+
+/// List the tensors in a .safetensors file
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The .safetensors file to list
+    pub file: PathBuf,
+
+    /// Also print the shape, data type and size of every tensor
+    #[arg(short, long)]
+    pub stats: bool,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or is not a valid .safetensors file.
+pub fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init_logging();
+    let Args { file: path, stats } = args;
+
+    let mut file = File::open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    let tensors = SafeTensors::deserialize(&buffer)?;
+    for (name, tensor) in tensors.tensors() {
+        println!("{name}");
+        if stats {
+            println!("Shape: {:?}", tensor.shape());
+            println!("Dtype: {:?}", tensor.dtype());
+            println!("Data size: {} bytes", tensor.data().len());
+            println!();
+        }
+    }
+    Ok(())
+}
@@ -1,33 +1,25 @@
-use safetensors::SafeTensors;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
-use std::env;
+// list-lora-blocks\src\main.rs
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    let stats_flag = args.contains(&String::from("--stats")) || args.contains(&String::from("-s"));
+// Standalone `list-lora-blocks` binary, the same tool is available as `dataset-tools list-lora-blocks`.
 
-    // Check if file path is provided
-    if args.len() < 2 {
-        return Err(
-            Box::new(std::io::Error::new(std::io::ErrorKind::InvalidInput, "No file path provided"))
-        );
-    }
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
 
-    let file_path = Path::new(&args[1]);
-    let mut file = File::open(file_path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    let tensors = SafeTensors::deserialize(&buffer)?;
-    for (name, tensor) in tensors.tensors() {
-        println!("{name}");
-        if stats_flag {
-            println!("Shape: {:?}", tensor.shape());
-            println!("Dtype: {:?}", tensor.dtype());
-            println!("Data size: {} bytes", tensor.data().len());
-            println!();
-        }
-    }
-    Ok(())
+use clap::Parser;
+use dataset_tools::CommonArgs;
+
+#[derive(Parser)]
+#[command(name = "list-lora-blocks", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: list_lora_blocks::Args,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    list_lora_blocks::run(cli.args, &cli.common)
 }
@@ -0,0 +1,80 @@
+// normalize-encoding\src\lib.rs
+
+// This program rewrites the caption files in a target directory and subdirectories as UTF-8
+// without a byte order mark and with LF line endings. The original encoding is detected per
+// file, so UTF-16 and Windows-1252 captions saved by Windows tools are converted too.
+//
+// Only .txt and .caption files are converted unless `--ext <ext>` is given, which can be
+// repeated. Pass `--dry-run` to list the files that would be converted, or `--journal <dir>`
+// to record the changes so they can be reverted with `undo <dir>`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use anyhow::{ bail, Result };
+use dataset_tools::{
+    decode_text,
+    DecodedText,
+    walk_directory_with,
+    write_to_file,
+    CommonArgs,
+    FileSelector,
+};
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Convert captions to UTF-8 without BOM and with LF line endings
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+
+    /// Extension of the files to convert, can be repeated
+    #[arg(long = "ext", value_name = "EXT", default_values = ["txt", "caption"])]
+    pub extensions: Vec<String>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory is invalid or any file fails to convert.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let target_dir = common.dir_or(args.directory);
+    if !target_dir.is_dir() {
+        bail!("{} is not a valid directory", target_dir.display());
+    }
+
+    println!("Normalizing .{} files in: {}", args.extensions.join(", ."), target_dir.display());
+
+    walk_directory_with(
+        &target_dir,
+        FileSelector::extensions(args.extensions),
+        &common.walk_options(),
+        normalize_file
+    ).await?.report()
+}
+
+async fn normalize_file(path: PathBuf) -> Result<()> {
+    let bytes = fs::read(&path).await?;
+    let decoded = decode_text(&bytes);
+    if decoded.is_normalized() {
+        return Ok(());
+    }
+
+    println!("{}: {}", path.display(), describe(&decoded));
+    write_to_file(&path, &decoded.text).await?;
+    Ok(())
+}
+
+fn describe(decoded: &DecodedText) -> String {
+    let mut found = vec![decoded.encoding.name().to_string()];
+    if decoded.had_bom {
+        found.push("BOM".to_string());
+    }
+    if decoded.had_crlf {
+        found.push("CRLF".to_string());
+    }
+    found.join(", ")
+}
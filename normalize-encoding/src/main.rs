@@ -0,0 +1,26 @@
+// normalize-encoding\src\main.rs
+
+// Standalone `normalize-encoding` binary, the same tool is available as `dataset-tools normalize-encoding`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use clap::Parser;
+use dataset_tools::CommonArgs;
+
+#[derive(Parser)]
+#[command(name = "normalize-encoding", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: normalize_encoding::Args,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    normalize_encoding::run(cli.args, &cli.common).await
+}
@@ -0,0 +1,63 @@
+// remove-escape-characters\src\lib.rs
+
+// This program removes escape characters from caption files in a target directory and subdirectories.
+//
+// Pass `--dry-run` to preview the changes as a diff, or `--journal <dir>` to record them
+// so they can be reverted with `undo <dir>`. `--backup` keeps the originals as `.bak` files.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use anyhow::{ bail, Result };
+use dataset_tools::{ walk_directory_with, read_file_content, write_to_file, CommonArgs };
+use std::path::PathBuf;
+
+/// Remove backslashes from caption files
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory is invalid or any file fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let target_dir = common.dir_or(args.directory);
+
+    if !target_dir.is_dir() {
+        bail!("{} is not a valid directory", target_dir.display());
+    }
+
+    let extension = common.config(&target_dir)?.caption_extension;
+    println!("Processing .{extension} files in: {}", target_dir.display());
+
+    let summary = walk_directory_with(
+        &target_dir,
+        extension.as_str(),
+        &common.walk_options(),
+        process_txt_file
+    ).await?;
+
+    println!("Finished processing all .{extension} files!");
+    summary.report()
+}
+
+async fn process_txt_file(path: PathBuf) -> Result<()> {
+    println!("Processing file: {}", path.display());
+
+    // Read the content of the file
+    let content = read_file_content(&path).await?;
+
+    // Remove all backslash characters
+    let processed_content = content.replace('\\', "");
+
+    // Write the processed content back to the file
+    write_to_file(&path, &processed_content).await?;
+
+    println!("Processed file: {}", path.display());
+    Ok(())
+}
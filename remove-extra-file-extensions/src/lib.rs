@@ -0,0 +1,32 @@
+// remove-extra-file-extensions\src\lib.rs
+
+// This program removes extra file extensions from .txt files in a target directory and subdirectories.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use dataset_tools::{ walk_directory_with, rename_file_without_image_extension, CommonArgs };
+use std::path::PathBuf;
+
+/// Rename `image.png.txt` captions to `image.txt`
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for .txt files
+    pub directory: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any file fails to be renamed.
+pub async fn run(args: Args, common: &CommonArgs) -> anyhow::Result<()> {
+    common.init()?;
+    let dir = common.dir_or(args.directory);
+
+    walk_directory_with(&dir, "txt", &common.walk_options(), |path| {
+        async move {
+            rename_file_without_image_extension(&path).await.map_err(anyhow::Error::from)
+        }
+    }).await?.report()
+}
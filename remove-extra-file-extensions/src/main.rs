@@ -1,25 +1,26 @@
 // remove-extra-file-extensions\src\main.rs
 
-// This program removes extra file extensions from .txt files in a target directory and subdirectories.
+// Standalone `remove-extra-file-extensions` binary, the same tool is available as `dataset-tools remove-extra-file-extensions`.
 
 // Turn clippy into a real nerd
 #![warn(clippy::all, clippy::pedantic)]
 
-use dataset_tools::{ walk_directory, rename_file_without_image_extension };
-use std::env;
-use std::path::Path;
+use clap::Parser;
+use dataset_tools::CommonArgs;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let dir = args.get(1).map_or("E:/training_dir_staging", String::as_str);
+#[derive(Parser)]
+#[command(name = "remove-extra-file-extensions", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
 
-    walk_directory(Path::new(dir), "txt", |path| {
-        let path_buf = path.to_path_buf();
-        async move {
-            rename_file_without_image_extension(&path_buf).await.map_err(anyhow::Error::from)
-        }
-    }).await?;
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: remove_extra_file_extensions::Args,
+}
 
-    Ok(())
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    remove_extra_file_extensions::run(cli.args, &cli.common).await
 }
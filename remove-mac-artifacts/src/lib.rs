@@ -0,0 +1,71 @@
+// remove-mac-artifacts\src\lib.rs
+
+// This program removes the `__MACOSX` folders and `.DS_Store` files that macOS leaves in
+// archives and shared folders. Removed files are journaled like any other change, whole
+// `__MACOSX` folders are not.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::path::{ Path, PathBuf };
+use tokio::fs;
+use walkdir::{ DirEntry, WalkDir };
+use anyhow::Result;
+use dataset_tools::{ journal, remove_file, CommonArgs };
+
+/// Remove `__MACOSX` folders and `.DS_Store` files
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to clean up
+    pub directory: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if a removal task panics.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let target_dir = common.dir_or(args.directory);
+    remove_macos_artifacts(&target_dir).await?;
+    println!("Finished removing macOS artifacts.");
+    Ok(())
+}
+
+async fn remove_macos_artifacts(target_dir: &Path) -> Result<()> {
+    let mut tasks = Vec::new();
+
+    for entry in WalkDir::new(target_dir).follow_links(true).into_iter().filter_map(Result::ok) {
+        if is_macos_artifact(&entry) {
+            let path = entry.path().to_owned();
+            let task = tokio::spawn(async move {
+                if path.is_dir() {
+                    if journal::is_dry_run() {
+                        println!("Would remove directory: {}", path.display());
+                    } else if let Err(e) = fs::remove_dir_all(&path).await {
+                        eprintln!("Failed to remove directory {}: {}", path.display(), e);
+                    } else {
+                        println!("Removed directory: {}", path.display());
+                    }
+                } else if let Err(e) = remove_file(&path).await {
+                    eprintln!("Failed to remove file {}: {}", path.display(), e);
+                } else if !journal::is_dry_run() {
+                    println!("Removed file: {}", path.display());
+                }
+            });
+            tasks.push(task);
+        }
+    }
+
+    for task in tasks {
+        task.await?;
+    }
+
+    Ok(())
+}
+
+fn is_macos_artifact(entry: &DirEntry) -> bool {
+    let file_name = entry.file_name().to_string_lossy();
+    file_name == "__MACOSX" || file_name == ".DS_Store"
+}
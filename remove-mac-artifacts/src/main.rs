@@ -1,49 +1,26 @@
-use std::path::Path;
-use tokio::fs;
-use walkdir::{ DirEntry, WalkDir };
-use anyhow::Result;
+// remove-mac-artifacts\src\main.rs
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let target_dir = std::env::current_dir()?;
-    remove_macos_artifacts(&target_dir).await?;
-    println!("Finished removing macOS artifacts.");
-    Ok(())
-}
+// Standalone `remove-mac-artifacts` binary, the same tool is available as `dataset-tools remove-mac-artifacts`.
 
-async fn remove_macos_artifacts(target_dir: &Path) -> Result<()> {
-    let mut tasks = Vec::new();
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
 
-    for entry in WalkDir::new(target_dir).follow_links(true).into_iter().filter_map(Result::ok) {
-        if is_macos_artifact(&entry) {
-            let path = entry.path().to_owned();
-            let task = tokio::spawn(async move {
-                if path.is_dir() {
-                    if let Err(e) = fs::remove_dir_all(&path).await {
-                        eprintln!("Failed to remove directory {}: {}", path.display(), e);
-                    } else {
-                        println!("Removed directory: {}", path.display());
-                    }
-                } else {
-                    if let Err(e) = fs::remove_file(&path).await {
-                        eprintln!("Failed to remove file {}: {}", path.display(), e);
-                    } else {
-                        println!("Removed file: {}", path.display());
-                    }
-                }
-            });
-            tasks.push(task);
-        }
-    }
+use clap::Parser;
+use dataset_tools::CommonArgs;
 
-    for task in tasks {
-        task.await?;
-    }
+#[derive(Parser)]
+#[command(name = "remove-mac-artifacts", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
 
-    Ok(())
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: remove_mac_artifacts::Args,
 }
 
-fn is_macos_artifact(entry: &DirEntry) -> bool {
-    let file_name = entry.file_name().to_string_lossy();
-    file_name == "__MACOSX" || file_name == ".DS_Store"
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    remove_mac_artifacts::run(cli.args, &cli.common).await
 }
@@ -0,0 +1,83 @@
+// remove-transparency\src\lib.rs
+
+// This program removes the alpha channel from PNG files in a target directory and subdirectories.
+//
+// Pass `--dry-run` to only list the images that would be rewritten, or `--journal <dir>` to
+// keep the original images so they can be restored with `undo <dir>`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::{ io::Cursor, path::{ Path, PathBuf } };
+use image::{ GenericImageView, ImageBuffer, ImageFormat, Rgba };
+use dataset_tools::{
+    walk_directory_blocking,
+    is_image_file,
+    journal,
+    write_bytes_to_file,
+    CommonArgs,
+};
+use tokio::runtime::Handle;
+use anyhow::{ Context, Result };
+
+/// Replace fully transparent pixels in PNG files with opaque black
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Target directory to process PNG files, same as `--dir`
+    #[arg(short, long)]
+    pub target: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any image fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+
+    let target_dir = common.dir_or(args.target);
+    println!("Processing PNG files in: {}", target_dir.display());
+
+    let handle = Handle::current();
+    let summary = walk_directory_blocking(&target_dir, "png", &common.walk_options(), move |path| {
+        process_image(&handle, &path)
+    }).await?;
+
+    summary.report()?;
+    println!("All PNG files processed successfully.");
+    Ok(())
+}
+
+fn process_image(handle: &Handle, path: &Path) -> Result<()> {
+    if !is_image_file(path) {
+        return Ok(());
+    }
+
+    println!("Processing image: {}", path.display());
+
+    let img = image::open(path).context("Failed to open image")?;
+    let (width, height) = img.dimensions();
+
+    let mut new_image = ImageBuffer::new(width, height);
+
+    for (x, y, pixel) in img.pixels() {
+        let new_pixel = if pixel[3] == 0 {
+            Rgba([0, 0, 0, 255]) // Black, fully opaque
+        } else {
+            pixel
+        };
+        new_image.put_pixel(x, y, new_pixel);
+    }
+
+    let mut buf = Vec::new();
+    new_image
+        .write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)
+        .context("Failed to encode image")?;
+    handle.block_on(write_bytes_to_file(path, &buf)).context("Failed to save image")?;
+    if !journal::is_dry_run() {
+        println!("Processed and saved: {}", path.display());
+    }
+
+    Ok(())
+}
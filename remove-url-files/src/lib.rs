@@ -0,0 +1,40 @@
+// remove-url-files\src\lib.rs
+
+// This program searches for .URL files in a target directory and deletes them.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::path::PathBuf;
+use anyhow::Result;
+use dataset_tools::{ journal, remove_file, walk_directory_with, CommonArgs };
+
+/// Delete .url shortcut files
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for .url files
+    pub directory: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any file fails to be removed.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let target_dir = common.dir_or(args.directory);
+
+    println!("Searching for .URL files in: {}", target_dir.display());
+
+    walk_directory_with(&target_dir, "url", &common.walk_options(), |path| async move {
+        remove_file(&path).await?;
+        if !journal::is_dry_run() {
+            println!("Removed: {}", path.display());
+        }
+        Ok(())
+    }).await?.report()?;
+
+    println!("Search complete.");
+    Ok(())
+}
@@ -1,23 +1,26 @@
-// delete-url-files/src/main.rs
+// remove-url-files\src\main.rs
 
-// This program searches for .URL files in a target directory and deletes them.
+// Standalone `remove-url-files` binary, the same tool is available as `dataset-tools remove-url-files`.
 
 // Turn clippy into a real nerd
 #![warn(clippy::all, clippy::pedantic)]
 
-use std::env;
-use std::path::Path;
-use dataset_tools::delete_files_with_extension;
+use clap::Parser;
+use dataset_tools::CommonArgs;
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let target_dir = if args.len() > 1 { &args[1] } else { "." };
-
-    println!("Searching for .URL files in: {target_dir}");
+#[derive(Parser)]
+#[command(name = "remove-url-files", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
 
-    delete_files_with_extension(Path::new(target_dir), "url").await?;
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: remove_url_files::Args,
+}
 
-    println!("Search complete.");
-    Ok(())
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    remove_url_files::run(cli.args, &cli.common).await
 }
@@ -0,0 +1,90 @@
+// rename-to-md5\src\lib.rs
+
+// This program renames every image in a directory to the MD5 hash of its content, moving its
+// caption, JSON and latent files along with it.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::path::PathBuf;
+use anyhow::{ bail, Context, Result };
+use dataset_tools::{ journal, Dataset, Sample, file_md5, rename_file, CommonArgs };
+
+/// Rename images and their sidecar files to the MD5 hash of the image
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory of images to rename
+    pub directory: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory is invalid or cannot be scanned.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let target_dir = common.dir_or(args.directory);
+    if !target_dir.is_dir() {
+        bail!("The specified path is not a directory.");
+    }
+
+    println!("Processing directory: {}", target_dir.display());
+
+    let dataset = Dataset::scan_with(&target_dir, &common.walk_options()).await?;
+    for sample in &dataset {
+        process_sample(sample).await?;
+    }
+
+    for orphan in dataset.orphans() {
+        println!("Skipping file without an image: {}", orphan.path.display());
+    }
+
+    println!("Finished processing all files.");
+    Ok(())
+}
+
+async fn process_sample(sample: &Sample) -> Result<()> {
+    let path = &sample.image;
+    println!("Examining image: {}", path.display());
+
+    let md5_sum = file_md5(path).await.context("Failed to read file")?;
+    println!("Calculated MD5: {md5_sum}");
+
+    let new_name = format!(
+        "{}.{}",
+        md5_sum,
+        path.extension().unwrap_or_default().to_str().unwrap_or("")
+    );
+    let new_path = path.with_file_name(&new_name);
+
+    println!("Attempting to rename: {} -> {}", path.display(), new_path.display());
+
+    // Under --dry-run, `rename_file` prints what it would do instead
+    let dry_run = journal::is_dry_run();
+    match rename_file(path, &new_path).await {
+        Ok(()) if dry_run => {}
+        Ok(()) => println!("Successfully renamed: {} -> {}", path.display(), new_path.display()),
+        Err(e) => {
+            println!("Failed to rename {}: {}", path.display(), e);
+            return Ok(());
+        }
+    }
+
+    // Move every associated caption, JSON and latent file along with the image
+    for sidecar in &sample.sidecars {
+        let new_sidecar_path = sample.renamed_sidecar(sidecar, &md5_sum);
+        match rename_file(&sidecar.path, &new_sidecar_path).await {
+            Ok(()) if dry_run => {}
+            Ok(()) =>
+                println!(
+                    "Renamed associated file: {} -> {}",
+                    sidecar.path.display(),
+                    new_sidecar_path.display()
+                ),
+            Err(e) => println!("Failed to rename {}: {}", sidecar.path.display(), e),
+        }
+    }
+
+    Ok(())
+}
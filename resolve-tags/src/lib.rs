@@ -0,0 +1,75 @@
+// resolve-tags\src\lib.rs
+
+// This program resolves the tags of existing captions against the `tag_aliases` and
+// `tag_implications` exports of the e621 database, downloaded from
+// https://e621.net/db_export/.
+//
+// Aliased tags are renamed to their canonical tag, and a tag that ends up in a caption twice
+// is kept once. With `--drop-implied`, tags implied by another tag of the same caption are
+// removed too, such as `canine` next to `wolf`. Keep tokens and sentences are left alone.
+//
+// Pass `--dry-run` to preview the changes as a diff, or `--journal <dir>` to record them so
+// they can be reverted with `undo <dir>`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use anyhow::Result;
+use dataset_tools::{
+    read_file_content,
+    walk_directory_with,
+    write_to_file,
+    Caption,
+    CommonArgs,
+    TagRelations,
+};
+use std::{ path::PathBuf, sync::Arc };
+
+/// Rename aliased e621 tags in captions and optionally drop implied tags
+#[derive(clap::Args, Debug)]
+#[command(group = clap::ArgGroup::new("exports").required(true).multiple(true))]
+pub struct Args {
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+
+    /// The `tag_aliases` export of the e621 database
+    #[arg(long, value_name = "CSV", group = "exports")]
+    pub aliases: Option<PathBuf>,
+
+    /// The `tag_implications` export of the e621 database, used by `--drop-implied`
+    #[arg(long, value_name = "CSV", group = "exports")]
+    pub implications: Option<PathBuf>,
+
+    /// Remove tags implied by another tag of the caption
+    #[arg(long, requires = "implications")]
+    pub drop_implied: bool,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if an export cannot be loaded, the directory cannot be walked or any
+/// caption fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    let config = common.config(&directory)?;
+    let relations = Arc::new(
+        TagRelations::load(args.aliases.as_deref(), args.implications.as_deref())?
+    );
+    let drop_implied = args.drop_implied;
+
+    let captions = config.caption_extension.as_str();
+    walk_directory_with(&directory, captions, &common.walk_options(), |path| {
+        let relations = Arc::clone(&relations);
+        async move {
+            let mut caption = Caption::parse(&read_file_content(&path).await?);
+            if relations.resolve_caption(&mut caption, drop_implied) {
+                println!("Resolved tags: {}", path.display());
+                write_to_file(&path, &caption.to_string()).await?;
+            }
+            Ok(())
+        }
+    }).await?.report()
+}
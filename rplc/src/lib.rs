@@ -0,0 +1,315 @@
+// rplc\src\lib.rs
+
+// This program replaces a string in all caption files in a target directory and subdirectories.
+// It can also replace some special characters with their keyboard-friendly versions.
+//
+// Usage:
+// - String replacement: ./rplc.exe <search_string> <replace_string> [target_dir]
+// - Special character replacement: ./rplc.exe --apostrophes [target_dir]
+//
+// The search string is literal unless `--regex` is given, in which case the replacement can
+// refer to capture groups as `$1` or `${name}`. `--word` only matches whole words and
+// `--ignore-case` matches regardless of case. Files are selected by the caption extension, or
+// by the globs given with `--glob`, relative to the target directory.
+//
+// The target directory can also be given with `--dir` and defaults to the current directory.
+//
+// A coloured diff of every change is shown and confirmed before anything is written. Pass
+// `--yes` to write without asking, `--dry-run` to only print the diff, or `--journal <dir>` to
+// record the changes so they can be reverted with `undo <dir>`.
+//
+// Examples:
+// ./rplc.exe "foo" "bar" ./my_directory
+// ./rplc.exe --word --ignore-case "cat" "feline" ./my_directory
+// ./rplc.exe --regex "(\w+) fur" "\${1}_fur" --glob "*.caption" ./my_directory
+// ./rplc.exe --apostrophes ./my_directory
+
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::{ io::Write, path::{ PathBuf, Path }, sync::{ Arc, Mutex } };
+use anyhow::{ bail, Result, Context };
+use dataset_tools::{
+    walk_directory_with,
+    format_text_content,
+    read_file_content,
+    journal,
+    write_to_file,
+    CommonArgs,
+    FileSelector,
+};
+use globset::{ Glob, GlobSetBuilder };
+use regex::{ NoExpand, Regex, RegexBuilder };
+
+/// Replace a string in every caption file
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Replace typographic apostrophes and quotes with their ASCII versions instead
+    #[arg(long, conflicts_with_all = ["regex", "word", "ignore_case"])]
+    pub apostrophes: bool,
+
+    /// String to search for, or the target directory with `--apostrophes`
+    #[arg(required_unless_present = "apostrophes")]
+    pub search: Option<String>,
+
+    /// String to replace it with, an empty replacement also collapses repeated spaces
+    #[arg(required_unless_present = "apostrophes")]
+    pub replace: Option<String>,
+
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub matching: MatchOptions,
+
+    /// Select files with a glob relative to the directory, such as `*.caption`, instead of the
+    /// caption extension, can be repeated
+    #[arg(short, long = "glob", value_name = "GLOB")]
+    pub globs: Vec<String>,
+
+    /// Write the changes without showing their diff and asking for confirmation first
+    #[arg(short, long, alias = "no-preview")]
+    pub yes: bool,
+}
+
+/// How the search string is matched.
+#[derive(clap::Args, Debug, Clone, Copy, Default)]
+pub struct MatchOptions {
+    /// Treat the search string as a regular expression, the replacement can refer to capture
+    /// groups as `$1` or `${name}`
+    #[arg(short, long)]
+    pub regex: bool,
+
+    /// Only match whole words, so `cat` does not match `catgirl`
+    #[arg(short, long)]
+    pub word: bool,
+
+    /// Match regardless of case
+    #[arg(short, long)]
+    pub ignore_case: bool,
+}
+
+/// A compiled search and replacement.
+#[derive(Debug)]
+pub struct Replacer {
+    /// Patterns and their replacements, applied in order.
+    replacements: Vec<(Regex, String)>,
+    /// Whether replacements can refer to capture groups.
+    expand: bool,
+    /// Whether repeated spaces are collapsed afterwards, for empty replacements.
+    collapse_spaces: bool,
+}
+
+impl Replacer {
+    /// Compiles a search and replacement. The search is literal unless `options.regex` is set,
+    /// in which case the replacement can refer to its capture groups.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the search is not a valid regex.
+    pub fn new(search: &str, replace: &str, options: MatchOptions) -> Result<Self> {
+        let pattern = if options.regex { search.to_string() } else { regex::escape(search) };
+        let pattern = if options.word { format!(r"\b(?:{pattern})\b") } else { pattern };
+        let pattern = RegexBuilder::new(&pattern)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .with_context(|| format!("Invalid search pattern: {search}"))?;
+        Ok(Self {
+            replacements: vec![(pattern, replace.to_string())],
+            expand: options.regex,
+            collapse_spaces: replace.is_empty(),
+        })
+    }
+
+    /// Replaces typographic apostrophes and quotes with their ASCII versions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the patterns fail to compile.
+    pub fn apostrophes() -> Result<Self> {
+        Ok(Self {
+            replacements: vec![
+                (Regex::new("[\u{2018}\u{2019}\u{201B}]")?, "'".to_string()),
+                (Regex::new("[\u{201C}\u{201D}\u{201F}]")?, "\"".to_string())
+            ],
+            expand: false,
+            collapse_spaces: false,
+        })
+    }
+
+    /// Returns the content with every match replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if collapsing repeated spaces fails.
+    pub fn replace(&self, content: &str) -> Result<String> {
+        let mut content = content.to_string();
+        for (pattern, replace) in &self.replacements {
+            let replaced = if self.expand {
+                pattern.replace_all(&content, replace.as_str())
+            } else {
+                pattern.replace_all(&content, NoExpand(replace))
+            };
+            content = replaced.into_owned();
+        }
+        if self.collapse_spaces {
+            content = format_text_content(&content)?;
+        }
+        Ok(content)
+    }
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the arguments are invalid, the directory cannot be walked or any file
+/// fails to process.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+
+    let (replacer, target_dir) = if args.apostrophes {
+        if args.replace.is_some() {
+            bail!("--apostrophes takes at most one directory");
+        }
+        let target_dir = common.dir_or(args.directory.or(args.search.map(PathBuf::from)));
+        println!("Replacing special characters in {}...", target_dir.display());
+        (Replacer::apostrophes()?, target_dir)
+    } else {
+        let (Some(search_string), Some(replace_string)) = (args.search, args.replace) else {
+            bail!("Both a search and a replace string are required");
+        };
+        let replacer = Replacer::new(&search_string, &replace_string, args.matching)?;
+        let target_dir = common.dir_or(args.directory);
+        println!(
+            "Replacing '{search_string}' with '{replace_string}' in {}...",
+            target_dir.display()
+        );
+        (replacer, target_dir)
+    };
+
+    let selector = if args.globs.is_empty() {
+        FileSelector::from(common.config(&target_dir)?.caption_extension.as_str())
+    } else {
+        glob_selector(&target_dir, &args.globs)?
+    };
+
+    let replacer = Arc::new(replacer);
+    if args.yes {
+        walk_directory_with(&target_dir, selector, &common.walk_options(), move |path| {
+            let replacer = Arc::clone(&replacer);
+            async move { process_file(&path, &replacer).await }
+        }).await?.report()?;
+    } else {
+        preview_and_apply(&target_dir, selector, replacer, common).await?;
+    }
+
+    println!("Processing complete.");
+    Ok(())
+}
+
+/// Selects the files matching any of the globs, relative to `directory`.
+fn glob_selector(directory: &Path, globs: &[String]) -> Result<FileSelector> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).with_context(|| format!("Invalid glob: {glob}"))?);
+    }
+    let globs = builder.build().context("Failed to build globs")?;
+    let directory = directory.to_path_buf();
+    Ok(FileSelector::predicate(move |path| {
+        globs.is_match(path.strip_prefix(&directory).unwrap_or(path))
+    }))
+}
+
+async fn process_file(path: &Path, replacer: &Replacer) -> Result<()> {
+    let content = read_file_content(path).await?;
+    let new_content = replacer.replace(&content)?;
+
+    if content != new_content {
+        write_to_file(path, &new_content).await?;
+        if !journal::is_dry_run() {
+            println!("Updated: {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes every change first, prints their diffs and only writes them once confirmed.
+async fn preview_and_apply(
+    target_dir: &Path,
+    selector: FileSelector,
+    replacer: Arc<Replacer>,
+    common: &CommonArgs
+) -> Result<()> {
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&changes);
+    walk_directory_with(target_dir, selector, &common.walk_options(), move |path| {
+        let replacer = Arc::clone(&replacer);
+        let changes = Arc::clone(&collected);
+        async move {
+            let content = read_file_content(&path).await?;
+            let new_content = replacer.replace(&content)?;
+            if content != new_content {
+                changes.lock().unwrap().push((path, content, new_content));
+            }
+            Ok(())
+        }
+    }).await?.report()?;
+
+    let mut changes = std::mem::take(&mut *changes.lock().unwrap());
+    if changes.is_empty() {
+        println!("No matches found.");
+        return Ok(());
+    }
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    for (path, content, new_content) in &changes {
+        journal::print_diff(path, "replaced", content, new_content);
+    }
+    if journal::is_dry_run() {
+        return Ok(());
+    }
+
+    print!("Apply the changes to {} files? [y/N] ", changes.len());
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).context("Failed to read the answer")?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        println!("No files were changed.");
+        return Ok(());
+    }
+
+    for (path, _, new_content) in &changes {
+        write_to_file(path, new_content).await?;
+        println!("Updated: {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace() {
+        let literal = Replacer::new("cat", "dog", MatchOptions::default()).unwrap();
+        assert_eq!(literal.replace("cat, catgirl, Cat, $1").unwrap(), "dog, doggirl, Cat, $1");
+
+        let options = MatchOptions { word: true, ignore_case: true, ..MatchOptions::default() };
+        let word = Replacer::new("cat", "$1", options).unwrap();
+        assert_eq!(word.replace("cat, catgirl, bobcat, Cat").unwrap(), "$1, catgirl, bobcat, $1");
+
+        let options = MatchOptions { regex: true, ..MatchOptions::default() };
+        let regex = Replacer::new(r"(\w+) fur", "${1}_fur", options).unwrap();
+        assert_eq!(regex.replace("white fur, blue eyes").unwrap(), "white_fur, blue eyes");
+        assert!(Replacer::new("(", "", options).is_err());
+        // Regex characters are literal without `--regex`
+        assert!(Replacer::new("(", "", MatchOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_apostrophes() {
+        let replacer = Replacer::apostrophes().unwrap();
+        let content = "\u{2018}it\u{2019}s\u{2019} \u{201C}fine\u{201D}";
+        assert_eq!(replacer.replace(content).unwrap(), "'it's' \"fine\"");
+    }
+}
@@ -1,4 +1,11 @@
-//use dataset_tools::walk_directory;
+// sample-browser\src\main.rs
+
+// A small window listing the .wav and .ogg samples below a directory, playing a sample when
+// its button is clicked. It stays a standalone binary rather than a `dataset-tools`
+// subcommand, as it needs a window and an audio device.
+
+use clap::Parser;
+use dataset_tools::CommonArgs;
 use egui::{ CentralPanel, ScrollArea, Ui };
 use std::path::{ Path, PathBuf };
 use std::sync::{ Arc, Mutex };
@@ -7,6 +14,17 @@ use std::fs::File;
 use std::io::BufReader;
 use walkdir::WalkDir;
 
+/// Browse and play the audio samples of a directory
+#[derive(Parser)]
+#[command(name = "sample-browser", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Directory to search for .wav and .ogg files
+    directory: Option<PathBuf>,
+}
+
 #[derive(Clone)]
 struct AudioPlayer {
     audio_files: Arc<Mutex<Vec<PathBuf>>>,
@@ -28,8 +46,7 @@ impl AudioPlayer {
         }
     }
 
-    async fn load_audio_files(&self) -> anyhow::Result<()> {
-        let audio_dir = Path::new("E:\\Audio");
+    async fn load_audio_files(&self, audio_dir: &Path) -> anyhow::Result<()> {
         let audio_files = self.audio_files.clone();
 
         for entry in WalkDir::new(audio_dir)
@@ -67,7 +84,10 @@ impl AudioPlayer {
     }
 }
 
-fn main() -> Result<(), eframe::Error> {
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    cli.common.init_logging();
+    let audio_dir = cli.common.dir_or(cli.directory);
     let audio_player = AudioPlayer::new();
 
     // Load audio files asynchronously
@@ -76,7 +96,7 @@ fn main() -> Result<(), eframe::Error> {
         ::new()
         .unwrap()
         .block_on(async move {
-            if let Err(e) = audio_player_clone.load_audio_files().await {
+            if let Err(e) = audio_player_clone.load_audio_files(&audio_dir).await {
                 eprintln!("Error loading audio files: {}", e);
             }
         });
@@ -86,7 +106,7 @@ fn main() -> Result<(), eframe::Error> {
         "Sample Browser",
         options,
         Box::new(|_cc| Ok(Box::new(MyApp::new(audio_player))))
-    )
+    ).map_err(|e| anyhow::anyhow!("Failed to open the window: {e}"))
 }
 
 struct MyApp {
@@ -0,0 +1,70 @@
+// search-for-superscript-numbers\src\lib.rs
+
+// This program searches for superscript numbers in Markdown files and reports the
+// matches.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use crossterm::{ ExecutableCommand, style::Print };
+use crossterm::style::{ Color, SetForegroundColor, ResetColor };
+use regex::Regex;
+use std::io::{ stdout, Write };
+use tokio::fs::File;
+use tokio::io::{ AsyncBufReadExt, BufReader };
+use std::path::PathBuf;
+use dataset_tools::{ walk_directory_with, CommonArgs };
+use anyhow::Result;
+
+/// Find superscript numbers in Markdown files
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for .md files
+    pub directory: Option<PathBuf>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or any file cannot be read.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let re = Regex::new(r"[¹²³⁴⁵⁶⁷⁸⁹]")?;
+    let dir = common.dir_or(args.directory);
+
+    walk_directory_with(&dir, "md", &common.walk_options(), |path| {
+        let value = re.clone();
+        async move {
+            let file = File::open(&path).await?;
+            let reader = BufReader::new(file);
+            let mut lines = reader.lines();
+
+            let mut index = 1;
+            while let Some(line) = lines.next_line().await? {
+                if let Some(mat) = value.find(&line) {
+                    let prefix = &line[..mat.start()];
+                    let match_str = mat.as_str();
+                    let suffix = &line[mat.end()..];
+
+                    // Files are searched concurrently, keep each match on its own line
+                    let mut out = stdout().lock();
+                    out.execute(SetForegroundColor(Color::Magenta))?;
+                    out.execute(Print(format!("{}:", path.display())))?;
+                    out.execute(SetForegroundColor(Color::Green))?;
+                    out.execute(Print(format!("{index}: ")))?;
+                    out.execute(ResetColor)?;
+                    out.execute(Print(prefix))?;
+                    out.execute(SetForegroundColor(Color::Red))?;
+                    out.execute(Print(match_str))?;
+                    out.execute(ResetColor)?;
+                    out.execute(Print(suffix))?;
+                    out.write_all(b"\n")?;
+                }
+                index += 1;
+            }
+
+            Ok(())
+        }
+    }).await?.report()
+}
@@ -0,0 +1,123 @@
+// simpletuner2kohya\src\lib.rs
+
+// This program converts the JSON captions written by SimpleTuner, which hold the file name of
+// an image and its caption, into caption files next to the images for kohya's scripts. The
+// caption is flattened to one line, its sentences are separated by commas and it can be
+// prefixed with an artist tag.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::{ path::{ Path, PathBuf }, sync::Arc };
+use anyhow::{ bail, Context, Result };
+use serde_json::Value;
+use dataset_tools::{ read_file_content, walk_directory_with, write_to_file, CommonArgs, Skipped };
+
+/// Convert the JSON captions of simpletuner to kohya caption files
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for JSON files
+    pub directory: Option<PathBuf>,
+
+    /// Artist to credit at the start of every caption, as `by <artist>`
+    #[arg(long)]
+    pub by: Option<String>,
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory does not exist, cannot be walked or any file fails to
+/// convert.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    if !directory.is_dir() {
+        bail!("The directory {} does not exist.", directory.display());
+    }
+    let extension = Arc::new(common.config(&directory)?.caption_extension);
+    let artist = Arc::new(args.by);
+
+    walk_directory_with(&directory, "json", &common.walk_options(), move |path| {
+        let extension = Arc::clone(&extension);
+        let artist = Arc::clone(&artist);
+        async move { convert(&path, &extension, artist.as_deref()).await }
+    }).await?.report()
+}
+
+/// Writes the caption of a simpletuner JSON file next to its image.
+async fn convert(path: &Path, extension: &str, artist: Option<&str>) -> Result<()> {
+    let json: Value = serde_json::from_str(&read_file_content(path).await?)
+        .with_context(|| format!("Invalid JSON {}", path.display()))?;
+    let (Some(filename), Some(caption)) = (json["filename"].as_str(), json["caption"].as_str())
+    else {
+        return Err(Skipped("No SimpleTuner caption found".to_string()).into());
+    };
+
+    let mut caption = caption.replace('\n', " ").replace("**", "").replace('\\', "");
+    if let Some(artist) = artist {
+        caption = format!("by {artist}, {caption}");
+    }
+    let caption = add_comma_after_period(&caption);
+
+    let caption_path = path.with_file_name(filename).with_extension(extension);
+    write_to_file(&caption_path, &caption).await?;
+    println!("Converted {} to {}", path.display(), caption_path.display());
+    Ok(())
+}
+
+/// Turns the sentences of a caption into comma-separated parts: commas other than the one after
+/// the artist tag are removed, and a comma is added after every period that is neither next to a
+/// quote nor after a digit. Periods after digits are removed.
+fn add_comma_after_period(text: &str) -> String {
+    // Remove all commas except for the artist tag
+    let text = match text.find("by ") {
+        Some(artist_tag) => {
+            let (before, after) = text.split_at(artist_tag);
+            match after.split_once(',') {
+                Some((artist, rest)) => {
+                    format!("{}{artist},{}", before.replace(',', ""), rest.replace(',', ""))
+                }
+                None => format!("{}{after}", before.replace(',', "")),
+            }
+        }
+        None => text.replace(',', ""),
+    };
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c != '.' {
+            result.push(c);
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        if previous.is_some_and(char::is_numeric) {
+            continue;
+        }
+        result.push('.');
+        let quoted = [previous, chars.get(i + 1).copied()]
+            .into_iter()
+            .any(|c| matches!(c, Some('"' | '\'')));
+        if !quoted {
+            result.push(',');
+        }
+    }
+
+    // Strip out excessive space characters and the comma after the last sentence
+    result.split_whitespace().collect::<Vec<_>>().join(" ").trim_end_matches(',').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_comma_after_period() {
+        assert_eq!(
+            add_comma_after_period("by alex, A fox, red. It says \"hi.\"  Version 2.5."),
+            "by alex, A fox red., It says \"hi.\" Version 25"
+        );
+    }
+}
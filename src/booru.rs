@@ -0,0 +1,430 @@
+// src/booru.rs
+
+// Booru metadata sources
+//
+// Every booru describes its posts differently. e621 groups the tags by category under
+// `post.tags`, Danbooru has one space-separated `tag_string_<category>` field per category,
+// and Gelbooru and Rule34 put every tag in a single `tags` string, in JSON or XML dumps of one
+// or more posts. Ratings differ too: Danbooru's `s` is sensitive, while e621's `s` is safe.
+//
+// A `BooruSource` parses the metadata files of one booru into `BooruPost`s, which hold what
+// the converters need in one shape: the MD5 and file name of the image, the rating, the score
+// and favorites, the tags by category, and the description and pool names where the booru has
+// them. Sources without categories put every tag under `general`. `Booru` selects a source on
+// the command line.
+
+use std::collections::BTreeMap;
+use anyhow::{ Context, Result };
+use clap::ValueEnum;
+use serde_json::{ Map, Value };
+
+/// The rating of a post, as every booru has some form of it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rating {
+    Safe,
+    #[default]
+    Questionable,
+    Explicit,
+}
+
+impl Rating {
+    /// Parses a rating letter or name as e621, Gelbooru and Rule34 write it: `s`, `q`, `e`,
+    /// `safe`, `questionable` or `explicit`. Gelbooru's `general` is safe and its `sensitive`
+    /// questionable, and anything unknown is questionable.
+    #[must_use = "Parses the rating and the result should be used"]
+    pub fn parse(rating: &str) -> Self {
+        match rating.trim().to_lowercase().as_str() {
+            "s" | "safe" | "g" | "general" => Self::Safe,
+            "e" | "explicit" => Self::Explicit,
+            _ => Self::Questionable,
+        }
+    }
+
+    /// Returns the name of the rating: `safe`, `questionable` or `explicit`.
+    #[must_use = "Returns the rating name and the result should be used"]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Safe => "safe",
+            Self::Questionable => "questionable",
+            Self::Explicit => "explicit",
+        }
+    }
+}
+
+/// A post of any booru, with what the converters use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BooruPost {
+    pub id: Option<u64>,
+    /// The MD5 of the image, lowercase.
+    pub md5: Option<String>,
+    /// The file name of the image without its extension, from its URL.
+    pub file_stem: Option<String>,
+    pub rating: Rating,
+    pub score: i64,
+    pub favorites: i64,
+    /// The tags by category, each in the order of the booru.
+    pub tags: BTreeMap<String, Vec<String>>,
+    /// The description of the post in the markup of the booru, `DText` for e621.
+    pub description: Option<String>,
+    /// The names of the pools of the post, if the metadata has them.
+    pub pools: Vec<String>,
+}
+
+/// Parses the metadata files of a booru.
+pub trait BooruSource: Send + Sync {
+    /// Returns the extensions of the metadata files, without the dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Parses the content of a metadata file with the given extension into its posts.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Vec<BooruPost>>` - The posts, or `None` if the file is not post metadata, such
+    ///   as tagger output or a config file next to the posts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content is not valid JSON or XML.
+    fn parse(&self, content: &str, extension: &str) -> Result<Option<Vec<BooruPost>>>;
+}
+
+/// e621 JSON, either a post as `{"post": {...}}` or a page as `{"posts": [...]}`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct E621;
+
+impl BooruSource for E621 {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn parse(&self, content: &str, _extension: &str) -> Result<Option<Vec<BooruPost>>> {
+        let data: Value = serde_json::from_str(content).context("Invalid JSON")?;
+        let posts = match (data.get("post"), data.get("posts")) {
+            (Some(post), _) => vec![post],
+            (None, Some(Value::Array(posts))) => posts.iter().collect(),
+            _ => {
+                return Ok(None);
+            }
+        };
+        Ok(Some(posts.into_iter().map(e621_post).collect()))
+    }
+}
+
+/// Danbooru JSON, either a post or an array of posts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Danbooru;
+
+impl BooruSource for Danbooru {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn parse(&self, content: &str, _extension: &str) -> Result<Option<Vec<BooruPost>>> {
+        let data: Value = serde_json::from_str(content).context("Invalid JSON")?;
+        let posts = match &data {
+            Value::Array(posts) => posts.iter().collect(),
+            _ => vec![&data],
+        };
+        // Every Danbooru post has its general tags, other JSON files do not
+        let posts = posts_with(posts, "tag_string_general");
+        Ok(posts.map(|posts| posts.into_iter().map(danbooru_post).collect()))
+    }
+}
+
+/// Gelbooru and Rule34 dumps, as JSON or XML, of one or more posts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gelbooru;
+
+impl BooruSource for Gelbooru {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json", "xml"]
+    }
+
+    fn parse(&self, content: &str, extension: &str) -> Result<Option<Vec<BooruPost>>> {
+        if extension.eq_ignore_ascii_case("xml") {
+            return gelbooru_xml(content);
+        }
+        let data: Value = serde_json::from_str(content).context("Invalid JSON")?;
+        // Gelbooru wraps the posts in `post`, Rule34 returns the array itself
+        let posts = match data.get("post").unwrap_or(&data) {
+            Value::Array(posts) => posts.iter().collect(),
+            post => vec![post],
+        };
+        let posts = posts_with(posts, "tags");
+        Ok(posts.map(|posts| posts.into_iter().map(gelbooru_post).collect()))
+    }
+}
+
+/// The boorus whose metadata can be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Booru {
+    /// e621 JSON
+    #[default]
+    E621,
+    /// Danbooru JSON with `tag_string_<category>` fields
+    Danbooru,
+    /// Gelbooru JSON or XML
+    Gelbooru,
+    /// Rule34 JSON or XML, in the format of Gelbooru
+    Rule34,
+}
+
+impl Booru {
+    /// Returns the source that parses the metadata of the booru.
+    #[must_use = "Returns the source and the result should be used"]
+    pub fn source(self) -> Box<dyn BooruSource> {
+        match self {
+            Self::E621 => Box::new(E621),
+            Self::Danbooru => Box::new(Danbooru),
+            Self::Gelbooru | Self::Rule34 => Box::new(Gelbooru),
+        }
+    }
+}
+
+/// Returns the objects that have a field every post of the booru has, or `None` if there are
+/// none, as the file then holds something else.
+fn posts_with<'a>(values: Vec<&'a Value>, field: &str) -> Option<Vec<&'a Value>> {
+    let posts: Vec<&Value> = values
+        .into_iter()
+        .filter(|value| value.get(field).is_some())
+        .collect();
+    Some(posts).filter(|posts| !posts.is_empty())
+}
+
+/// Returns a field as a string, also if it is a number.
+fn string_field(post: &Value, name: &str) -> Option<String> {
+    match post.get(name)? {
+        Value::String(value) if !value.is_empty() => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns a field as a number, also if it is a string.
+fn number_field(post: &Value, name: &str) -> Option<i64> {
+    match post.get(name)? {
+        Value::Number(value) => value.as_i64(),
+        Value::String(value) => value.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Returns the file name without its extension of a file URL.
+fn url_stem(url: &str) -> Option<String> {
+    let name = url.rsplit('/').next()?.split(['?', '#']).next()?;
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    Some(stem.to_string()).filter(|stem| !stem.is_empty())
+}
+
+/// Splits a space-separated tag string.
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split_whitespace().map(str::to_string).collect()
+}
+
+/// Reads an e621 post from the API.
+fn e621_post(post: &Value) -> BooruPost {
+    let file = post.get("file").unwrap_or(&Value::Null);
+    let tags = post
+        .get("tags")
+        .and_then(Value::as_object)
+        .map(|categories| {
+            categories
+                .iter()
+                .filter_map(|(category, tags)| {
+                    let tags = tags.as_array()?.iter().filter_map(Value::as_str);
+                    Some((category.clone(), tags.map(str::to_string).collect()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    BooruPost {
+        id: post.get("id").and_then(Value::as_u64),
+        md5: string_field(file, "md5").map(|md5| md5.to_lowercase()),
+        file_stem: file.get("url").and_then(Value::as_str).and_then(url_stem),
+        rating: Rating::parse(post.get("rating").and_then(Value::as_str).unwrap_or("q")),
+        score: post
+            .get("score")
+            .and_then(|score| score.get("total").or(Some(score)))
+            .and_then(Value::as_i64)
+            .unwrap_or(0),
+        favorites: number_field(post, "fav_count").unwrap_or(0),
+        tags,
+        description: string_field(post, "description"),
+        pools: pool_names(post),
+    }
+}
+
+/// Reads the pool names of an e621 post. The API only lists pool IDs, which are skipped, while
+/// downloaders like gallery-dl can add the pools with their `name`.
+fn pool_names(post: &Value) -> Vec<String> {
+    let Some(Value::Array(pools)) = post.get("pools") else {
+        return Vec::new();
+    };
+    pools
+        .iter()
+        .filter_map(|pool| match pool {
+            Value::String(name) => Some(name.as_str()),
+            Value::Object(_) => pool.get("name").and_then(Value::as_str),
+            _ => None,
+        })
+        .map(|name| name.replace('_', " ").trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Reads a Danbooru post, whose ratings are `g`eneral, `s`ensitive, `q`uestionable and
+/// `e`xplicit.
+fn danbooru_post(post: &Value) -> BooruPost {
+    let rating = match post.get("rating").and_then(Value::as_str).unwrap_or("q") {
+        "g" => Rating::Safe,
+        "e" => Rating::Explicit,
+        _ => Rating::Questionable,
+    };
+    let tags = ["artist", "character", "copyright", "general", "meta"]
+        .into_iter()
+        .filter_map(|category| {
+            let tags = split_tags(&string_field(post, &format!("tag_string_{category}"))?);
+            Some((category.to_string(), tags)).filter(|(_, tags)| !tags.is_empty())
+        })
+        .collect();
+    BooruPost {
+        id: post.get("id").and_then(Value::as_u64),
+        md5: string_field(post, "md5").map(|md5| md5.to_lowercase()),
+        file_stem: string_field(post, "file_url").as_deref().and_then(url_stem),
+        rating,
+        score: number_field(post, "score").unwrap_or(0),
+        favorites: number_field(post, "fav_count").unwrap_or(0),
+        tags,
+        ..BooruPost::default()
+    }
+}
+
+/// Reads a Gelbooru or Rule34 post, whose fields can be numbers or strings. Tags are HTML
+/// escaped and have no category.
+fn gelbooru_post(post: &Value) -> BooruPost {
+    let tags = string_field(post, "tags")
+        .map(|tags| split_tags(&unescape_html(&tags)))
+        .filter(|tags| !tags.is_empty());
+    let md5 = string_field(post, "md5").or_else(|| string_field(post, "hash"));
+    BooruPost {
+        id: number_field(post, "id").and_then(|id| u64::try_from(id).ok()),
+        md5: md5.map(|md5| md5.to_lowercase()),
+        file_stem: string_field(post, "file_url")
+            .or_else(|| string_field(post, "image"))
+            .as_deref()
+            .and_then(url_stem),
+        rating: Rating::parse(&string_field(post, "rating").unwrap_or_default()),
+        score: number_field(post, "score").unwrap_or(0),
+        favorites: number_field(post, "fav_count").unwrap_or(0),
+        tags: tags.map(|tags| BTreeMap::from([("general".to_string(), tags)])).unwrap_or_default(),
+        ..BooruPost::default()
+    }
+}
+
+/// Reads the posts of a Gelbooru or Rule34 XML dump. Their fields are either attributes of
+/// the `post` elements or child elements.
+fn gelbooru_xml(content: &str) -> Result<Option<Vec<BooruPost>>> {
+    let document = roxmltree::Document::parse(content).context("Invalid XML")?;
+    let posts = document
+        .descendants()
+        .filter(|node| node.has_tag_name("post"))
+        .map(|node| {
+            let mut fields = Map::new();
+            for attribute in node.attributes() {
+                fields.insert(attribute.name().to_string(), attribute.value().into());
+            }
+            for child in node.children().filter(roxmltree::Node::is_element) {
+                let text = child.text().unwrap_or_default();
+                fields.insert(child.tag_name().name().to_string(), text.into());
+            }
+            gelbooru_post(&Value::Object(fields))
+        })
+        .collect::<Vec<_>>();
+    Ok(Some(posts).filter(|posts| !posts.is_empty()))
+}
+
+/// Replaces the HTML entities Gelbooru uses in tags.
+fn unescape_html(text: &str) -> String {
+    text.replace("&#039;", "'")
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_e621() {
+        let content = r#"{"post": {
+            "id": 42,
+            "file": { "md5": "ABCD", "url": "https://static1.e621.net/data/ab/cd/abcd.png" },
+            "rating": "e",
+            "score": { "total": 12 },
+            "fav_count": 7,
+            "tags": { "general": ["fur", "solo"], "species": ["red_fox"] },
+            "description": "A [b]fox[/b].",
+            "pools": [7, { "id": 8, "name": "fox_and_friends" }]
+        }}"#;
+        let posts = E621.parse(content, "json").unwrap().unwrap();
+        assert_eq!(posts.len(), 1);
+        let post = &posts[0];
+        assert_eq!((post.id, post.md5.as_deref()), (Some(42), Some("abcd")));
+        assert_eq!(post.file_stem.as_deref(), Some("abcd"));
+        assert_eq!((post.rating, post.score, post.favorites), (Rating::Explicit, 12, 7));
+        assert_eq!(post.tags["general"], ["fur", "solo"]);
+        assert_eq!(post.description.as_deref(), Some("A [b]fox[/b]."));
+        assert_eq!(post.pools, ["fox and friends"]);
+        assert!(E621.parse("{", "json").is_err());
+
+        // Other JSON files, such as tagger output, are not posts
+        assert_eq!(E621.parse("[]", "json").unwrap(), None);
+        assert_eq!(E621.parse(r#"{"tags": ["fox"]}"#, "json").unwrap(), None);
+    }
+
+    #[test]
+    fn test_danbooru() {
+        let content = r#"[{
+            "id": 1, "md5": "beef", "file_url": "https://cdn.donmai.us/original/be/ef/beef.jpg",
+            "rating": "s", "score": 30, "fav_count": 40,
+            "tag_string_general": "1girl solo", "tag_string_artist": "someone",
+            "tag_string_meta": ""
+        }]"#;
+        let post = &Danbooru.parse(content, "json").unwrap().unwrap()[0];
+        assert_eq!(post.rating, Rating::Questionable);
+        assert_eq!(post.file_stem.as_deref(), Some("beef"));
+        assert_eq!((post.score, post.favorites), (30, 40));
+        assert_eq!(post.tags.keys().collect::<Vec<_>>(), ["artist", "general"]);
+        assert_eq!(Danbooru.parse(r#"{"learning_rate": 1e-4}"#, "json").unwrap(), None);
+    }
+
+    #[test]
+    fn test_gelbooru() {
+        let json = r#"{"@attributes": {"count": 1}, "post": [{
+            "id": 7, "md5": "cafe", "file_url": "https://img3.gelbooru.com/images/ca/fe/cafe.png",
+            "rating": "general", "score": 5, "tags": "cat&#039;s_tail  solo"
+        }]}"#;
+        let post = &Gelbooru.parse(json, "json").unwrap().unwrap()[0];
+        assert_eq!((post.id, post.rating, post.score), (Some(7), Rating::Safe, 5));
+        assert_eq!(post.tags["general"], ["cat's_tail", "solo"]);
+
+        // Rule34 returns the array itself and names the MD5 `hash`
+        let json = r#"[{"id": 8, "hash": "F00D", "image": "f00d.jpg", "rating": "e", "tags": ""}]"#;
+        let post = &Gelbooru.parse(json, "json").unwrap().unwrap()[0];
+        assert_eq!((post.md5.as_deref(), post.file_stem.as_deref()), (Some("f00d"), Some("f00d")));
+        assert!(post.tags.is_empty());
+
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<posts count="2" offset="0">
+  <post id="9" md5="d00d" rating="q" score="3" tags=" fox &amp; hound " file_url="x/d00d.gif"/>
+  <post><id>10</id><md5>fade</md5><rating>explicit</rating><tags>wolf</tags></post>
+</posts>"#;
+        let posts = Gelbooru.parse(xml, "xml").unwrap().unwrap();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].tags["general"], ["fox", "&", "hound"]);
+        assert_eq!((posts[0].rating, posts[0].score), (Rating::Questionable, 3));
+        assert_eq!((posts[1].id, posts[1].rating), (Some(10), Rating::Explicit));
+    }
+}
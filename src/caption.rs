@@ -0,0 +1,536 @@
+// src/caption.rs
+
+// Caption syntax
+//
+// A caption is an optional keep-token prefix, a list of comma-separated tags and optional
+// natural-language sentences:
+//
+// ```text
+// feral, weasel ||| cat, dog, wolf \(character\), running, A weasel chases a cat.
+// ```
+//
+// The prefix ends at the first `|||`, as written by `keep-tokens`. Tags are split on commas,
+// except commas escaped as `\,` or inside parentheses such as `(tag, other:1.2)`, and their
+// whitespace is trimmed and collapsed. The sentences start at the first part that opens with
+// a whole sentence of at least three words ending in `.`, `!` or `?`, or at the first line
+// after a line break that reads like prose, such as `This is a description`. The older
+// `tags., sentences` form is understood too. Capitalised tags such as `Super Smash Bros
+// Melee` are only read as prose after a line break.
+//
+// A parsed caption remembers how it was written and prints itself back unchanged, so tools
+// that edit a few tags leave the rest of the file alone: unedited parts keep their text, and
+// edited tags are joined with the caption's own separator. Captions built in code, or after
+// `Caption::normalize`, print in the canonical form above. Sentences that open like a tag,
+// such as `a cat and a dog` or `Drawn by Alex, thanks!`, are printed in the `tags., sentences`
+// form, as they would be read back as tags otherwise.
+
+use std::{ convert::Infallible, fmt, str::FromStr };
+
+/// Separates the keep tokens from the rest of the caption.
+pub const KEEP_SEPARATOR: &str = "|||";
+
+/// A parsed caption.
+///
+/// Two captions are equal if their keep tokens, tags and sentences are, however they were
+/// written.
+#[derive(Debug, Clone, Default)]
+pub struct Caption {
+    /// Tokens before the `|||` separator, as written.
+    pub keep_tokens: Vec<String>,
+    /// Tags in caption order, as written, so escapes like `\(` are kept.
+    pub tags: Vec<String>,
+    /// Natural-language text after the tags, trimmed.
+    pub sentences: String,
+    /// How the caption was written, if it was parsed.
+    layout: Option<Layout>,
+}
+
+/// The text of a parsed caption around its parts.
+#[derive(Debug, Clone)]
+struct Layout {
+    /// The parts as parsed, to tell which ones were edited.
+    keep_tokens: Vec<String>,
+    tags: Vec<String>,
+    sentences: String,
+    /// Everything before the tags, such as the keep tokens and `|||`.
+    head: String,
+    /// Every tag as written, and all of them with their separators.
+    written_tags: Vec<String>,
+    tag_list: String,
+    /// The text between the first two tags, used to join edited tags.
+    separator: String,
+    /// The text between the tags and the sentences.
+    sentence_separator: String,
+    /// Everything after the caption, such as a final line break.
+    tail: String,
+}
+
+impl Caption {
+    /// Parses caption text. Every text is a valid caption, so this cannot fail.
+    #[must_use = "Parses a caption and the result should be used"]
+    pub fn parse(text: &str) -> Self {
+        let (keep_tokens, body_start) = match find_unescaped(text, KEEP_SEPARATOR) {
+            Some(i) => (split_tags(&text[..i]), i + KEEP_SEPARATOR.len()),
+            None => (Vec::new(), 0),
+        };
+
+        // Tags and sentences are found as byte ranges of the text, trimmed
+        let mut tag_ranges = Vec::new();
+        let mut sentences_start = None;
+        let mut line_break = false;
+        for (start, end, next) in segments(&text[body_start..]) {
+            let (start, end) = trim_range(text, body_start + start, body_start + end);
+            let segment = &text[start..end];
+            if segment.is_empty() {
+                line_break |= next == Some('\n');
+                continue;
+            }
+            if opens_with_sentence(segment) || (line_break && is_prose(segment)) {
+                sentences_start = Some(start);
+                break;
+            }
+            // The legacy `tags., sentences` form ends the tags with a period
+            let legacy_tag = legacy_last_tag(segment, next);
+            tag_ranges.push((start, start + legacy_tag.unwrap_or(segment).len()));
+            if legacy_tag.is_some() {
+                sentences_start = Some(end + 1);
+                break;
+            }
+            line_break = next == Some('\n');
+        }
+        let tags_range = tag_ranges
+            .first()
+            .zip(tag_ranges.last())
+            .map(|(&(start, _), &(_, end))| (start, end));
+        let sentences_range = sentences_start
+            .map(|start| trim_range(text, start, text.len()))
+            .filter(|(start, end)| start < end);
+
+        let content_end = match (tags_range, sentences_range) {
+            (_, Some((_, end))) | (Some((_, end)), None) => end,
+            (None, None) => text.trim_end().len(),
+        };
+        let head_end = tags_range.or(sentences_range).map_or(content_end, |(start, _)| start);
+        let written_tags: Vec<String> = tag_ranges
+            .iter()
+            .map(|&(start, end)| text[start..end].to_string())
+            .collect();
+        let tags: Vec<String> = written_tags.iter().map(|tag| normalize_whitespace(tag)).collect();
+        let sentences = sentences_range.map_or("", |(start, end)| &text[start..end]).to_string();
+        let layout = Layout {
+            keep_tokens: keep_tokens.clone(),
+            tags: tags.clone(),
+            sentences: sentences.clone(),
+            head: text[..head_end].to_string(),
+            separator: match tag_ranges[..] {
+                [(_, end), (start, _), ..] => text[end..start].to_string(),
+                _ => ", ".to_string(),
+            },
+            written_tags,
+            tag_list: tags_range.map_or("", |(start, end)| &text[start..end]).to_string(),
+            sentence_separator: match (tags_range, sentences_range) {
+                (Some((_, end)), Some((start, _))) => text[end..start].to_string(),
+                _ => String::new(),
+            },
+            tail: text[content_end..].to_string(),
+        };
+
+        Self { keep_tokens, tags, sentences, layout: Some(layout) }
+    }
+
+    /// Creates a caption holding only the given tags, escaping each of them.
+    #[must_use = "Creates a caption and the result should be used"]
+    pub fn from_tags<I, S>(tags: I) -> Self where I: IntoIterator<Item = S>, S: AsRef<str> {
+        Self {
+            tags: tags
+                .into_iter()
+                .map(|tag| escape_tag(tag.as_ref()))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Forgets how the caption was written, so it prints in the canonical form.
+    pub fn normalize(&mut self) {
+        self.layout = None;
+    }
+
+    /// Returns `true` if the caption has no keep tokens, tags or sentences.
+    #[must_use = "Determines if the caption is empty and the result should be checked"]
+    pub fn is_empty(&self) -> bool {
+        self.keep_tokens.is_empty() && self.tags.is_empty() && self.sentences.is_empty()
+    }
+
+    /// Returns the tags without escapes, in caption order.
+    pub fn tag_names(&self) -> impl Iterator<Item = String> + '_ {
+        self.tags.iter().map(|tag| unescape_tag(tag))
+    }
+
+    /// Returns `true` if the caption has the tag, comparing without escapes.
+    #[must_use = "Determines if the caption has a tag and the result should be checked"]
+    pub fn has_tag(&self, name: &str) -> bool {
+        let name = unescape_tag(name);
+        self.tag_names().any(|tag| tag == name)
+    }
+
+    /// Removes every occurrence of a tag, comparing without escapes.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the tag was present.
+    pub fn remove_tag(&mut self, name: &str) -> bool {
+        let name = unescape_tag(name);
+        let len = self.tags.len();
+        self.tags.retain(|tag| unescape_tag(tag) != name);
+        self.tags.len() != len
+    }
+
+    /// Writes an e621 tag the way the other tags of the caption are written: with underscores
+    /// if they use them, and with escaped parentheses if they escape them.
+    #[must_use = "Returns the styled tag and the result should be used"]
+    pub fn styled_tag(&self, name: &str) -> String {
+        let tags = &self.tags;
+        let underscores = tags.iter().any(|tag| tag.contains('_')) &&
+            !tags.iter().any(|tag| tag.contains(' '));
+        let tag = if underscores { name.to_string() } else { name.replace('_', " ") };
+        // Commas are escaped either way, as they would split the tag otherwise
+        if tags.iter().any(|tag| tag.contains('\\')) {
+            escape_tag(&tag)
+        } else {
+            tag.replace(',', "\\,")
+        }
+    }
+}
+
+impl PartialEq for Caption {
+    fn eq(&self, other: &Self) -> bool {
+        self.keep_tokens == other.keep_tokens &&
+            self.tags == other.tags &&
+            self.sentences == other.sentences
+    }
+}
+
+impl Eq for Caption {}
+
+impl FromStr for Caption {
+    type Err = Infallible;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse(text))
+    }
+}
+
+impl fmt::Display for Caption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let layout = self.layout.as_ref();
+        let has_body = !self.tags.is_empty() || !self.sentences.is_empty();
+        match layout {
+            Some(layout) if layout.keep_tokens == self.keep_tokens &&
+                (!layout.tags.is_empty() || !layout.sentences.is_empty()) == has_body => {
+                f.write_str(&layout.head)?;
+            }
+            _ if !self.keep_tokens.is_empty() => {
+                write!(f, "{} {KEEP_SEPARATOR}", self.keep_tokens.join(", "))?;
+                if has_body {
+                    f.write_str(" ")?;
+                }
+            }
+            _ => {}
+        }
+
+        match layout {
+            Some(layout) if layout.tags == self.tags => f.write_str(&layout.tag_list)?,
+            _ => {
+                // Tags that were not edited keep the text they were written as
+                let separator = layout.map_or(", ", |layout| &layout.separator);
+                let mut unused: Vec<(&String, &String)> = layout
+                    .map(|layout| layout.tags.iter().zip(&layout.written_tags).collect())
+                    .unwrap_or_default();
+                for (i, tag) in self.tags.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(separator)?;
+                    }
+                    match unused.iter().position(|(parsed, _)| *parsed == tag) {
+                        Some(j) => f.write_str(unused.remove(j).1)?,
+                        None => f.write_str(tag)?,
+                    }
+                }
+            }
+        }
+
+        if !self.sentences.is_empty() {
+            if !self.tags.is_empty() {
+                match layout {
+                    Some(layout) if layout.sentences == self.sentences &&
+                        !layout.sentence_separator.is_empty() => {
+                        f.write_str(&layout.sentence_separator)?;
+                    }
+                    _ => f.write_str(if opens_with_prose(&self.sentences) { ", " } else { "., " })?,
+                }
+            }
+            f.write_str(&self.sentences)?;
+        }
+        layout.map_or(Ok(()), |layout| f.write_str(&layout.tail))
+    }
+}
+
+/// Escapes the characters of a tag that have a meaning in captions: parentheses, which
+/// trainers read as emphasis, and commas.
+#[must_use = "Returns the escaped tag and the result should be used"]
+pub fn escape_tag(tag: &str) -> String {
+    let mut escaped = String::with_capacity(tag.len());
+    for c in tag.chars() {
+        if matches!(c, '\\' | '(' | ')' | ',') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Removes the backslash escapes from a tag.
+#[must_use = "Returns the unescaped tag and the result should be used"]
+pub fn unescape_tag(tag: &str) -> String {
+    let mut unescaped = String::with_capacity(tag.len());
+    let mut chars = tag.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                unescaped.push(next);
+                continue;
+            }
+        }
+        unescaped.push(c);
+    }
+    unescaped
+}
+
+/// Returns the byte index of the first occurrence of `pattern` that is not escaped.
+fn find_unescaped(text: &str, pattern: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if text[i..].starts_with(pattern) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Splits text into `(start, end, separator)` byte ranges at commas and line breaks that are
+/// neither escaped nor inside parentheses.
+fn segments(text: &str) -> Vec<(usize, usize, Option<char>)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => {
+                escaped = true;
+            }
+            '(' => {
+                depth += 1;
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+            }
+            ',' | '\n' if depth == 0 => {
+                segments.push((start, i, Some(c)));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push((start, text.len(), None));
+    segments
+}
+
+/// Returns the byte range of `text[start..end]` without its surrounding whitespace.
+fn trim_range(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let part = &text[start..end];
+    let start = start + part.len() - part.trim_start().len();
+    (start, start + part.trim().len())
+}
+
+/// Splits a list of tags at the commas and line breaks that are neither escaped nor inside
+/// parentheses, trimming the tags and dropping empty ones. Escapes are kept.
+#[must_use = "Splits a list of tags and the result should be used"]
+pub fn split_tags(text: &str) -> Vec<String> {
+    segments(text)
+        .into_iter()
+        .map(|(start, end, _)| text[start..end].trim())
+        .filter(|tag| !tag.is_empty())
+        .map(normalize_whitespace)
+        .collect()
+}
+
+/// Returns `true` if a part of a caption opens with a sentence: at least three words up to a
+/// `.`, `!` or `?` that ends the part or is followed by whitespace.
+fn opens_with_sentence(segment: &str) -> bool {
+    segment
+        .char_indices()
+        .find(|&(i, c)| {
+            matches!(c, '.' | '!' | '?') &&
+                segment[i + 1..].chars().next().is_none_or(char::is_whitespace)
+        })
+        .is_some_and(|(i, _)| segment[..i].split_whitespace().count() >= 3)
+}
+
+/// Returns `true` if a line of a caption reads like prose rather than a tag.
+fn is_prose(segment: &str) -> bool {
+    let words = segment.split_whitespace().count();
+    if words < 2 {
+        return false;
+    }
+    segment.ends_with(['.', '!', '?']) || (words >= 4 && segment.starts_with(char::is_uppercase))
+}
+
+/// Returns `true` if the first part of some sentences opens with a sentence, so they are
+/// found after the tags without the `tags., sentences` form.
+fn opens_with_prose(sentences: &str) -> bool {
+    segments(sentences)
+        .into_iter()
+        .map(|(start, end, _)| sentences[start..end].trim())
+        .find(|segment| !segment.is_empty())
+        .is_some_and(opens_with_sentence)
+}
+
+/// Returns the tag without its period if the segment ends the tags of a `tags., sentences`
+/// caption.
+fn legacy_last_tag(segment: &str, next: Option<char>) -> Option<&str> {
+    if next != Some(',') {
+        return None;
+    }
+    let tag = segment.strip_suffix('.')?;
+    tag.ends_with(|c: char| c.is_alphanumeric() || c == ')').then_some(tag)
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keep_tokens_tags_and_sentences() {
+        let caption = Caption::parse("feral, cat, dog, weasel, running\nThis is a description");
+        assert!(caption.keep_tokens.is_empty());
+        assert_eq!(caption.tags, ["feral", "cat", "dog", "weasel", "running"]);
+        assert_eq!(caption.sentences, "This is a description");
+
+        let text = "feral, weasel ||| cat,  long\thair ,,\nA cat runs fast, then stops.";
+        let caption = Caption::parse(text);
+        assert_eq!(caption.keep_tokens, ["feral", "weasel"]);
+        assert_eq!(caption.tags, ["cat", "long hair"]);
+        assert_eq!(caption.sentences, "A cat runs fast, then stops.");
+
+        let caption = Caption::parse("cat, dog, A cat chases the dog. It is fast, really.");
+        assert_eq!(caption.tags, ["cat", "dog"]);
+        assert_eq!(caption.sentences, "A cat chases the dog. It is fast, really.");
+    }
+
+    #[test]
+    fn test_parse_capitalised_tags() {
+        let caption = Caption::parse("fox, Super Smash Bros Melee, oh no!, solo, A fox sits.");
+        assert_eq!(caption.tags, ["fox", "Super Smash Bros Melee", "oh no!", "solo"]);
+        assert_eq!(caption.sentences, "A fox sits.");
+        assert!(caption.has_tag("solo"));
+
+        // After a line break, a capitalised line starts the sentences
+        let caption = Caption::parse("fox, solo\nSuper Smash Bros Melee is a game");
+        assert_eq!(caption.tags, ["fox", "solo"]);
+        assert_eq!(caption.sentences, "Super Smash Bros Melee is a game");
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        let caption = Caption::parse(r"wolf \(character\), hi\, there, (masterpiece, best:1.2)");
+        assert_eq!(caption.tags, [r"wolf \(character\)", r"hi\, there", "(masterpiece, best:1.2)"]);
+        assert!(caption.has_tag("wolf (character)"));
+        assert!(caption.has_tag("hi, there"));
+        assert_eq!(escape_tag("wolf (character)"), r"wolf \(character\)");
+    }
+
+    #[test]
+    fn test_parse_legacy_separator() {
+        let caption = Caption::parse("cat, dog., a cat and a dog");
+        assert_eq!(caption.tags, ["cat", "dog"]);
+        assert_eq!(caption.sentences, "a cat and a dog");
+        assert_eq!(caption.to_string(), "cat, dog., a cat and a dog");
+
+        // Sentences that would be read back as tags keep the form
+        let caption = Caption {
+            tags: vec!["fox".to_string()],
+            sentences: "Drawn by Alex, thanks!".to_string(),
+            ..Caption::default()
+        };
+        assert_eq!(caption.to_string(), "fox., Drawn by Alex, thanks!");
+        assert_eq!(Caption::parse(&caption.to_string()), caption);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for text in [
+            "",
+            "cat",
+            "feral, weasel |||",
+            "feral, weasel ||| cat, dog, running, This is a description",
+            r"wolf \(character\), hi\, there, A wolf howls at the moon.",
+            "An otter floats on its back.",
+        ] {
+            let caption = Caption::parse(text);
+            assert_eq!(caption.to_string(), text);
+            assert_eq!(Caption::parse(&caption.to_string()), caption);
+        }
+
+        // Other forms round-trip too, and normalizing prints the canonical form
+        for text in [
+            "cat, dog\nA wolf runs.",
+            "cat,dog",
+            "cat,\tlong\thair\t,\tdog\n",
+            " cat ,dog\nfox ||| wolf",
+        ] {
+            assert_eq!(Caption::parse(text).to_string(), text);
+        }
+        let mut caption = Caption::parse(" cat ,dog\nfox ||| wolf");
+        caption.normalize();
+        assert_eq!(caption.to_string(), "cat, dog, fox ||| wolf");
+        assert_eq!(Caption::parse(&caption.to_string()), caption);
+    }
+
+    #[test]
+    fn test_edit_keeps_layout() {
+        let mut caption = Caption::parse("cat,dog,\tlong\thair,fox\nA wolf runs.\n");
+        assert!(caption.remove_tag("dog"));
+        assert_eq!(caption.to_string(), "cat,long\thair,fox\nA wolf runs.\n");
+        caption.tags.push("snow".to_string());
+        assert_eq!(caption.to_string(), "cat,long\thair,fox,snow\nA wolf runs.\n");
+
+        // Edited sentences are separated the canonical way
+        caption.sentences = "a wolf".to_string();
+        assert_eq!(caption.to_string(), "cat,long\thair,fox,snow., a wolf\n");
+
+        let mut caption = Caption::parse("feral |||\n");
+        caption.tags.push("solo".to_string());
+        assert_eq!(caption.to_string(), "feral ||| solo\n");
+    }
+
+    #[test]
+    fn test_remove_tag() {
+        let mut caption = Caption::from_tags(["wolf (character)", "solo"]);
+        assert_eq!(caption.to_string(), r"wolf \(character\), solo");
+        assert!(caption.remove_tag("wolf (character)"));
+        assert!(!caption.remove_tag("canine"));
+        assert_eq!(caption.to_string(), "solo");
+    }
+}
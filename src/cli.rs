@@ -0,0 +1,111 @@
+// src/cli.rs
+
+// Command line arguments shared by every tool
+//
+// Each tool crate exposes its own `Args` and a `run(args, &CommonArgs)` function. Both the
+// standalone binaries and the `dataset-tools` multicall binary flatten `CommonArgs` next to
+// the tool's arguments, so `--dir`, `--dry-run`, `--jobs` and `--verbose` mean the same thing
+// everywhere.
+
+use std::{ num::NonZeroUsize, path::{ Path, PathBuf } };
+use anyhow::Result;
+use log::LevelFilter;
+
+use crate::{ journal, Config, WalkOptions, WriteOptions };
+
+/// Options accepted by every tool.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct CommonArgs {
+    /// Directory to process, instead of the positional directory argument
+    #[arg(short, long, global = true, value_name = "DIR")]
+    pub dir: Option<PathBuf>,
+
+    /// Read the settings from this file instead of the nearest `dataset-tools.toml`
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Print what would change instead of changing anything
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Record every change in this directory so the run can be reverted with `undo`
+    #[arg(long, global = true, value_name = "DIR")]
+    pub journal: Option<PathBuf>,
+
+    /// Keep a copy of every rewritten file as `<name>.bak`
+    #[arg(long, global = true)]
+    pub backup: bool,
+
+    /// Keep the modification time of rewritten files
+    #[arg(long, global = true)]
+    pub preserve_mtime: bool,
+
+    /// Number of files to process at the same time, defaults to the number of CPUs
+    #[arg(short, long, global = true, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+
+    /// Skip paths matching this gitignore-style glob, can be repeated
+    #[arg(long, global = true, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Log more details, repeat for even more
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+impl CommonArgs {
+    /// Sets up logging at the level chosen with `--verbose`. `RUST_LOG` still takes precedence.
+    pub fn init_logging(&self) {
+        let level = match self.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        };
+        // A second initialization only happens in tests and is harmless
+        let _ = env_logger::Builder::new().filter_level(level).parse_default_env().try_init();
+    }
+
+    /// Sets up logging, the journal and the write options for the current process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal cannot be created.
+    pub fn init(&self) -> Result<()> {
+        self.init_logging();
+        journal::init(self.dry_run, self.journal.as_deref())?;
+        WriteOptions { backup: self.backup, preserve_mtime: self.preserve_mtime }.install();
+        Ok(())
+    }
+
+    /// Returns the options for directory walks.
+    #[must_use = "Returns the walk options and the result should be used"]
+    pub fn walk_options(&self) -> WalkOptions {
+        let mut options = WalkOptions::default();
+        if let Some(jobs) = self.jobs {
+            options.jobs = jobs.get();
+        }
+        options.exclude.extend(self.exclude.iter().cloned());
+        options
+    }
+
+    /// Returns the directory to process: `--dir`, else the tool's positional directory, else
+    /// the current directory.
+    #[must_use = "Returns the directory to process and the result should be used"]
+    pub fn dir_or(&self, positional: Option<PathBuf>) -> PathBuf {
+        self.dir.clone().or(positional).unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    /// Returns the settings for `dir`: the `--config` file if given, else the nearest
+    /// `dataset-tools.toml` in `dir` or its parents, else the defaults.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration file cannot be read or is invalid.
+    pub fn config(&self, dir: &Path) -> Result<Config> {
+        match &self.config {
+            Some(path) => Config::load(path),
+            None => Config::discover(dir),
+        }
+    }
+}
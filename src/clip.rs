@@ -0,0 +1,197 @@
+// src/clip.rs
+
+// CLIP tokenizer
+//
+// Stable Diffusion and its relatives encode captions with the CLIP text model, whose context
+// holds 77 tokens: a start token, 75 tokens of text and an end token. Trainers silently cut
+// longer captions, so `ClipTokenizer` counts tokens exactly the way CLIP does, offline.
+//
+// CLIP uses byte-level BPE. Text is cleaned (whitespace collapsed, lowercased) and split into
+// words, numbers and runs of punctuation. The bytes of every piece are mapped to printable
+// characters, the last one marked with `</w>`, and adjacent symbols are merged by the rank of
+// the pair in the merges file until no known pair is left. Each remaining symbol is a token.
+//
+// The merges file is either `bpe_simple_vocab_16e6.txt` from the original CLIP repository,
+// once decompressed, or the `merges.txt` of a Hugging Face CLIP tokenizer. Both start with a
+// header line followed by one space-separated pair per line. HTML entities and the `ftfy`
+// text fixes of the Python tokenizer are not applied, which only matters for mangled text.
+
+use std::{ collections::HashMap, path::Path };
+use anyhow::{ bail, Context, Result };
+use regex::Regex;
+
+use crate::decode_text;
+
+/// The number of tokens in the CLIP context, including the start and end tokens.
+pub const CLIP_CONTEXT_LENGTH: usize = 77;
+
+/// The number of tokens of caption text that fit in the CLIP context.
+pub const CLIP_TOKEN_LIMIT: usize = CLIP_CONTEXT_LENGTH - 2;
+
+/// The number of merges CLIP uses, so its vocabulary holds 49408 tokens.
+const MERGE_COUNT: usize = 49152 - 256 - 2;
+
+/// Marks the last symbol of a word.
+const END_OF_WORD: &str = "</w>";
+
+/// Splits text into the pieces that are encoded separately.
+const PIECE_PATTERN: &str =
+    r"(?i)<\|startoftext\|>|<\|endoftext\|>|'s|'t|'re|'ve|'m|'ll|'d|\p{L}+|\p{N}|[^\s\p{L}\p{N}]+";
+
+/// A CLIP byte-level BPE tokenizer.
+#[derive(Debug, Clone)]
+pub struct ClipTokenizer {
+    /// Symbol pairs and their merge rank, lower merges first.
+    ranks: HashMap<(String, String), usize>,
+    /// The printable character standing for each byte.
+    byte_chars: Vec<char>,
+    pieces: Regex,
+}
+
+impl ClipTokenizer {
+    /// Loads the tokenizer from a merges file in any common encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a merges file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_merges(&decode_text(&bytes).text)
+            .with_context(|| format!("Invalid merges file {}", path.display()))
+    }
+
+    /// Creates the tokenizer from the content of a merges file, skipping its header line.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a line is not a pair of symbols.
+    pub fn from_merges(merges: &str) -> Result<Self> {
+        let mut ranks = HashMap::new();
+        for (i, line) in merges.lines().skip(1).take(MERGE_COUNT).enumerate() {
+            let Some((first, second)) = line.split_once(' ') else {
+                bail!("Expected a pair of symbols on line {}", i + 2);
+            };
+            ranks.entry((first.to_string(), second.to_string())).or_insert(i);
+        }
+        Ok(Self { ranks, byte_chars: byte_chars(), pieces: Regex::new(PIECE_PATTERN)? })
+    }
+
+    /// Splits text into CLIP tokens, without the start and end tokens.
+    #[must_use = "Tokenizes the text and the result should be used"]
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        self.pieces
+            .find_iter(&text)
+            .flat_map(|piece| {
+                let symbols: String = piece
+                    .as_str()
+                    .bytes()
+                    .map(|byte| self.byte_chars[usize::from(byte)])
+                    .collect();
+                self.bpe(&symbols)
+            })
+            .collect()
+    }
+
+    /// Returns the number of CLIP tokens in text, without the start and end tokens.
+    #[must_use = "Counts the tokens and the result should be used"]
+    pub fn count(&self, text: &str) -> usize {
+        self.tokenize(text).len()
+    }
+
+    /// Merges the symbols of a piece by rank until no known pair is left.
+    fn bpe(&self, piece: &str) -> Vec<String> {
+        let mut word: Vec<String> = piece.chars().map(String::from).collect();
+        if let Some(last) = word.last_mut() {
+            last.push_str(END_OF_WORD);
+        }
+
+        loop {
+            let best = word
+                .windows(2)
+                .enumerate()
+                .filter_map(|(i, pair)| {
+                    self.ranks.get(&(pair[0].clone(), pair[1].clone())).map(|&rank| (rank, i))
+                })
+                .min();
+            let Some((_, i)) = best else {
+                break;
+            };
+            let (first, second) = (word[i].clone(), word[i + 1].clone());
+
+            // Every occurrence of the pair is merged, from left to right
+            let mut merged = Vec::with_capacity(word.len());
+            let mut j = 0;
+            while j < word.len() {
+                if j + 1 < word.len() && word[j] == first && word[j + 1] == second {
+                    merged.push(format!("{first}{second}"));
+                    j += 2;
+                } else {
+                    merged.push(word[j].clone());
+                    j += 1;
+                }
+            }
+            word = merged;
+        }
+        word
+    }
+}
+
+/// Returns the printable character GPT-2 style byte-level BPE uses for each byte: printable
+/// Latin-1 characters stand for themselves, the other bytes are shifted past 255.
+fn byte_chars() -> Vec<char> {
+    let printable = |byte: u8| matches!(byte, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF);
+    let mut shifted = 0;
+    (0..=u8::MAX)
+        .map(|byte| {
+            if printable(byte) {
+                char::from(byte)
+            } else {
+                shifted += 1;
+                char::from_u32(255 + shifted).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MERGES: &str = "#version: 0.2
+c a
+ca t</w>
+d o
+do g</w>
+";
+
+    #[test]
+    fn test_tokenize() {
+        let tokenizer = ClipTokenizer::from_merges(MERGES).unwrap();
+        assert_eq!(tokenizer.tokenize("Cat"), ["cat</w>"]);
+        assert_eq!(tokenizer.tokenize("cats, dog"), ["ca", "t", "s</w>", ",</w>", "dog</w>"]);
+        assert_eq!(tokenizer.count("  a   cat's 12 "), 6);
+        assert!(ClipTokenizer::from_merges("#version: 0.2\nnot-a-pair\n").is_err());
+    }
+
+    #[test]
+    fn test_load_encodings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("merges.txt");
+        std::fs::write(&path, format!("\u{feff}{MERGES}")).unwrap();
+        assert_eq!(ClipTokenizer::load(&path).unwrap().tokenize("dog"), ["dog</w>"]);
+
+        let utf16: Vec<u8> = MERGES.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        std::fs::write(&path, utf16).unwrap();
+        assert_eq!(ClipTokenizer::load(&path).unwrap().tokenize("dog"), ["dog</w>"]);
+    }
+
+    #[test]
+    fn test_byte_chars() {
+        let chars = byte_chars();
+        assert_eq!(chars[usize::from(b'a')], 'a');
+        assert_eq!(chars[usize::from(b' ')], '\u{120}');
+        assert_eq!(chars[0], '\u{100}');
+    }
+}
@@ -0,0 +1,196 @@
+// src/config.rs
+
+// Per-dataset configuration
+//
+// Settings that differ between training sets live in a `dataset-tools.toml` file inside the
+// dataset instead of being compiled into the tools. The file is found by walking up from the
+// directory a tool runs on, so a dataset and all of its subfolders share one file, and the
+// nearest file wins. Every key is optional and falls back to the built-in default, and tools
+// let command line options override the file.
+//
+// ```toml
+// keep-tokens = ["feral", "weasel"]
+// ignored-tags = ['\bconditional_dnp\b', '^\d{4}$', '^\d+:\d+$']
+// tag-threshold = 0.2
+// caption-extension = "txt"
+// artist-prefix = "by "
+// category-order = ["artist", "character", "species", "general", "meta"]
+// excluded-categories = ["invalid", "lore"]
+// category-limits = { general = 40 }
+// tag-rules = "tag-rules.txt"
+// tag-rules-mode = "blacklist"
+// caption-style = "custom"
+// rating-template = "rating:{rating}"
+// score-template = "quality {score}"
+// ```
+//
+// Relative paths in the file are relative to the folder of the file.
+
+use std::{ collections::BTreeMap, path::{ Path, PathBuf } };
+use anyhow::{ Context, Result };
+use log::info;
+use serde::Deserialize;
+
+use crate::{ decode_text, e621::CaptionStyle, tag_filter::FilterMode };
+
+/// The name of the file holding the per-dataset configuration.
+pub const CONFIG_FILE_NAME: &str = "dataset-tools.toml";
+
+/// Settings shared by the tools working on a dataset.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Tokens moved to the front of every caption by `keep-tokens`.
+    pub keep_tokens: Vec<String>,
+    /// Regular expressions of tags left out of converted captions.
+    pub ignored_tags: Vec<String>,
+    /// Minimum probability for a tagger prediction to end up in the caption.
+    pub tag_threshold: f64,
+    /// Extension of caption files, without the dot.
+    pub caption_extension: String,
+    /// Text put in front of artist names in converted captions.
+    pub artist_prefix: String,
+    /// Order of the e621 tag categories in converted captions, after the rating. Categories
+    /// that are not listed follow in alphabetical order.
+    pub category_order: Vec<String>,
+    /// e621 tag categories left out of converted captions.
+    pub excluded_categories: Vec<String>,
+    /// Maximum number of tags taken from an e621 tag category, in post order.
+    pub category_limits: BTreeMap<String, usize>,
+    /// Rule file of tags to filter out of captions, see `tag_filter`.
+    pub tag_rules: Option<PathBuf>,
+    /// Whether the rule file names the tags to remove or the tags to keep.
+    pub tag_rules_mode: FilterMode,
+    /// How converted e621 captions write the rating and quality of a post.
+    pub caption_style: CaptionStyle,
+    /// The rating tag of the custom caption style, where `{rating}` is `safe`, `questionable`
+    /// or `explicit`. Without one, captions get no rating tag.
+    pub rating_template: Option<String>,
+    /// The quality tag of the custom caption style, where `{score}` is 9 for the best posts of
+    /// the dataset down to 4. Without one, captions get no quality tag.
+    pub score_template: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            keep_tokens: vec!["feral".to_string(), "weasel".to_string()],
+            ignored_tags: vec![
+                r"\bconditional_dnp\b".to_string(),
+                r"^\d{4}$".to_string(), // Years
+                r"^\d+:\d+$".to_string() // Aspect ratio
+            ],
+            tag_threshold: 0.2,
+            caption_extension: "txt".to_string(),
+            artist_prefix: "by ".to_string(),
+            category_order: ["artist", "character", "species", "general", "meta"]
+                .map(ToString::to_string)
+                .to_vec(),
+            excluded_categories: Vec::new(),
+            category_limits: BTreeMap::new(),
+            tag_rules: None,
+            tag_rules_mode: FilterMode::Blacklist,
+            caption_style: CaptionStyle::Plain,
+            rating_template: None,
+            score_template: None,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the nearest `dataset-tools.toml` in `dir` or one of its parents. For a file,
+    /// the search starts in its folder.
+    #[must_use = "Finds the configuration file and the result should be used"]
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        // Canonicalize so that relative paths like `.` can be walked up too
+        let dir = dir.canonicalize().ok()?;
+        dir.ancestors()
+            .map(|ancestor| ancestor.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Reads a configuration file in any common encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or contains unknown or invalid keys.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let content = decode_text(&bytes).text;
+        let mut config: Self = toml::from_str(&content)
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        if let (Some(rules), Some(dir)) = (&config.tag_rules, path.parent()) {
+            config.tag_rules = Some(dir.join(rules));
+        }
+        Ok(config)
+    }
+
+    /// Reads the nearest configuration file for `dir`, or returns the defaults if there is
+    /// none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration file found is invalid.
+    pub fn discover(dir: &Path) -> Result<Self> {
+        match Self::find(dir) {
+            Some(path) => {
+                info!("Using config {}", path.display());
+                Self::load(&path)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_discover_walks_up() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(Config::discover(&nested).unwrap(), Config::default());
+
+        fs::write(
+            temp_dir.path().join(CONFIG_FILE_NAME),
+            "keep-tokens = [\"canine\"]\ntag-threshold = 0.35\n\
+             category-limits = { general = 40 }\ntag-rules = \"rules.txt\"\n\
+             caption-style = \"pony\"\n"
+        ).unwrap();
+        let config = Config::discover(&nested).unwrap();
+        assert_eq!(config.keep_tokens, ["canine"]);
+        assert!((config.tag_threshold - 0.35).abs() < f64::EPSILON);
+        assert_eq!(config.category_limits.get("general"), Some(&40));
+        assert_eq!(config.caption_style, CaptionStyle::Pony);
+        // Relative paths are relative to the config file
+        assert_eq!(config.tag_rules, Some(temp_dir.path().join("rules.txt")));
+        // Keys missing from the file keep their defaults
+        assert_eq!(config.caption_extension, "txt");
+    }
+
+    #[test]
+    fn test_unknown_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, "keep-token = [\"canine\"]\n").unwrap();
+        assert!(Config::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_load_utf16() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(CONFIG_FILE_NAME);
+        let bytes: Vec<u8> = "\u{feff}keep-tokens = [\"canine\"]\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        fs::write(&path, bytes).unwrap();
+        assert_eq!(Config::load(&path).unwrap().keep_tokens, ["canine"]);
+    }
+}
@@ -0,0 +1,333 @@
+// src/dataset.rs
+
+// Dataset scanning
+//
+// A `Dataset` is a directory of training images where every image can have any number of
+// sidecar files next to it: captions (`.txt`, `.caption`), metadata (`.json`) and latent
+// caches (`.npz`). Scanning pairs each image with its sidecars once, so tools don't have to
+// re-derive "image + caption" with ad-hoc `with_extension` calls, and exposes the files that
+// could not be paired on either side.
+
+use std::{ collections::HashMap, path::{ Path, PathBuf } };
+use log::warn;
+use regex::Regex;
+use anyhow::{ Context, Result };
+use tokio::{ io, task };
+
+use crate::{ is_image_file, read_file_content, walk_builder, WalkOptions };
+
+/// The kind of a file that belongs to an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SidecarKind {
+    /// A caption file (`.txt` or `.caption`).
+    Caption,
+    /// A metadata file (`.json`), e.g. a booru post or tagger output.
+    Json,
+    /// A cached latent (`.npz`) written by the trainer.
+    Latents,
+}
+
+impl SidecarKind {
+    /// Returns the sidecar kind for a path based on its extension, if it is one.
+    #[must_use = "Determines the sidecar kind of a path and the result should be checked"]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "txt" | "caption" => Some(Self::Caption),
+            "json" => Some(Self::Json),
+            "npz" => Some(Self::Latents),
+            _ => None,
+        }
+    }
+}
+
+/// A file that belongs to an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sidecar {
+    pub path: PathBuf,
+    pub kind: SidecarKind,
+}
+
+/// An image together with all of its sidecar files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub image: PathBuf,
+    pub sidecars: Vec<Sidecar>,
+}
+
+impl Sample {
+    /// Returns the file stem of the image, which is the name shared by its sidecars.
+    #[must_use = "Returns the stem of the image and the result should be used"]
+    pub fn stem(&self) -> &str {
+        self.image
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+    }
+
+    /// Returns the sidecars of the given kind.
+    pub fn sidecars_of(&self, kind: SidecarKind) -> impl Iterator<Item = &Path> {
+        self.sidecars
+            .iter()
+            .filter(move |s| s.kind == kind)
+            .map(|s| s.path.as_path())
+    }
+
+    /// Returns the caption file of the sample, preferring `.txt` over `.caption`.
+    #[must_use = "Returns the caption path and the result should be used"]
+    pub fn caption_path(&self) -> Option<&Path> {
+        let mut captions: Vec<&Path> = self.sidecars_of(SidecarKind::Caption).collect();
+        captions.sort_by_key(|p| p.extension().is_none_or(|ext| ext != "txt"));
+        captions.first().copied()
+    }
+
+    /// Returns the caption file of the sample with the given extension, such as the
+    /// `caption-extension` of the config.
+    #[must_use = "Returns the caption path and the result should be used"]
+    pub fn caption_with_extension(&self, extension: &str) -> Option<&Path> {
+        self.sidecars_of(SidecarKind::Caption)
+            .find(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(extension)))
+    }
+
+    /// Returns the JSON metadata file of the sample.
+    #[must_use = "Returns the JSON path and the result should be used"]
+    pub fn json_path(&self) -> Option<&Path> {
+        self.sidecars_of(SidecarKind::Json).next()
+    }
+
+    /// Returns `true` if the sample has a caption file.
+    #[must_use = "Checks if the sample has a caption and the result should be checked"]
+    pub fn has_caption(&self) -> bool {
+        self.caption_path().is_some()
+    }
+
+    /// Reads the caption of the sample, if it has one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the caption file exists but cannot be read.
+    pub async fn read_caption(&self) -> io::Result<Option<String>> {
+        match self.caption_path() {
+            Some(path) => Ok(Some(read_file_content(path).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the path a sidecar would be moved to if the image was renamed to `new_stem`.
+    ///
+    /// The part of the sidecar name after the image stem is kept, so `foo.png.txt` and
+    /// `foo_1024x1024_sdxl.npz` become `bar.png.txt` and `bar_1024x1024_sdxl.npz`.
+    #[must_use = "Returns the renamed sidecar path and the result should be used"]
+    pub fn renamed_sidecar(&self, sidecar: &Sidecar, new_stem: &str) -> PathBuf {
+        let name = sidecar.path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let suffix = name.strip_prefix(self.stem()).unwrap_or(name);
+        sidecar.path.with_file_name(format!("{new_stem}{suffix}"))
+    }
+}
+
+/// A directory of images paired with their sidecar files.
+#[derive(Debug, Clone, Default)]
+pub struct Dataset {
+    root: PathBuf,
+    samples: Vec<Sample>,
+    orphans: Vec<Sidecar>,
+}
+
+impl Dataset {
+    /// Scans a directory and its subdirectories, pairing every image with its sidecars.
+    /// Skips hidden entries and anything excluded by `.datasetignore` files.
+    ///
+    /// A sidecar belongs to an image in the same directory if its file stem is the image's
+    /// stem (`foo.txt`), the image's file name (`foo.png.txt`) or, for latent caches, the
+    /// image's stem followed by a resolution suffix (`foo_1024x1024_sdxl.npz`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be walked.
+    pub async fn scan(root: impl AsRef<Path>) -> Result<Self> {
+        Self::scan_with(root, &WalkOptions::default()).await
+    }
+
+    /// Scans a directory like [`Dataset::scan`], also skipping the `--exclude` globs of
+    /// `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be walked or an exclude glob is invalid.
+    pub async fn scan_with(root: impl AsRef<Path>, options: &WalkOptions) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let options = options.clone();
+        task::spawn_blocking(move || Self::scan_blocking(root, &options)).await.context(
+            "Failed to scan dataset"
+        )?
+    }
+
+    fn scan_blocking(root: PathBuf, options: &WalkOptions) -> Result<Self> {
+        let mut images = Vec::new();
+        let mut sidecars = Vec::new();
+
+        let mut walker = walk_builder(&root, options)?;
+        walker.sort_by_file_name(std::ffi::OsStr::cmp);
+        for entry in walker.build() {
+            let entry = entry.with_context(|| format!("Failed to walk {}", root.display()))?;
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let path = entry.into_path();
+            if is_image_file(&path) {
+                images.push(path);
+            } else if let Some(kind) = SidecarKind::from_path(&path) {
+                sidecars.push(Sidecar { path, kind });
+            }
+        }
+
+        let mut samples: Vec<Sample> = images
+            .into_iter()
+            .map(|image| Sample { image, sidecars: Vec::new() })
+            .collect();
+
+        // Index every sample by both its stem and its full file name, per directory
+        let mut index: HashMap<(PathBuf, String), usize> = HashMap::new();
+        for (i, sample) in samples.iter().enumerate() {
+            let dir = sample.image.parent().unwrap_or(Path::new("")).to_path_buf();
+            let name = sample.image.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            let stem = sample.stem();
+            if let Some(&other) = index.get(&(dir.clone(), stem.to_string())) {
+                warn!(
+                    "{} shares its stem with {}, sidecars are paired with the latter",
+                    sample.image.display(),
+                    samples[other].image.display()
+                );
+            } else {
+                index.insert((dir.clone(), stem.to_string()), i);
+            }
+            index.insert((dir, name.to_string()), i);
+        }
+
+        let latent_suffix = Regex::new(r"^(.+)_\d+x\d+(_[A-Za-z0-9]+)?$")?;
+        let mut orphans = Vec::new();
+        for sidecar in sidecars {
+            let dir = sidecar.path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let stem = sidecar.path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_string();
+
+            let mut owner = index.get(&(dir.clone(), stem.clone()));
+            if owner.is_none() && sidecar.kind == SidecarKind::Latents {
+                if let Some(caps) = latent_suffix.captures(&stem) {
+                    owner = index.get(&(dir, caps[1].to_string()));
+                }
+            }
+
+            match owner {
+                Some(&i) => samples[i].sidecars.push(sidecar),
+                None => orphans.push(sidecar),
+            }
+        }
+
+        Ok(Self { root, samples, orphans })
+    }
+
+    /// Returns the directory the dataset was scanned from.
+    #[must_use = "Returns the dataset root and the result should be used"]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns all images in the dataset together with their sidecars.
+    #[must_use = "Returns the samples of the dataset and the result should be used"]
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Returns an iterator over the samples of the dataset.
+    pub fn iter(&self) -> std::slice::Iter<'_, Sample> {
+        self.samples.iter()
+    }
+
+    /// Returns the images that have no caption file.
+    pub fn uncaptioned(&self) -> impl Iterator<Item = &Sample> {
+        self.samples.iter().filter(|s| !s.has_caption())
+    }
+
+    /// Returns the sidecar files that could not be paired with any image.
+    #[must_use = "Returns the orphaned sidecars and the result should be used"]
+    pub fn orphans(&self) -> &[Sidecar] {
+        &self.orphans
+    }
+
+    /// Returns the number of images in the dataset.
+    #[must_use = "Returns the number of samples and the result should be used"]
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns `true` if the dataset contains no images.
+    #[must_use = "Checks if the dataset is empty and the result should be checked"]
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Dataset {
+    type Item = &'a Sample;
+    type IntoIter = std::slice::Iter<'a, Sample>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.samples.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("assets")
+    }
+
+    #[tokio::test]
+    async fn test_scan_assets() {
+        let dataset = Dataset::scan(assets()).await.unwrap();
+        assert_eq!(dataset.len(), 4);
+        assert!(dataset.orphans().is_empty());
+
+        let uncaptioned: Vec<_> = dataset.uncaptioned().map(|s| s.image.clone()).collect();
+        assert_eq!(uncaptioned, vec![
+            assets().join("1_no_caption").join("input.png"),
+            assets().join("input.png"),
+        ]);
+
+        let extra = dataset.samples()
+            .iter()
+            .find(|s| s.image.starts_with(assets().join("1_caption_with_extra_extension")))
+            .unwrap();
+        assert_eq!(
+            extra.caption_path(),
+            Some(assets().join("1_caption_with_extra_extension").join("input.png.txt").as_path())
+        );
+    }
+
+    #[test]
+    fn test_renamed_sidecar() {
+        let sample = Sample {
+            image: PathBuf::from("dir/foo.png"),
+            sidecars: vec![],
+        };
+        let txt = Sidecar { path: PathBuf::from("dir/foo.png.txt"), kind: SidecarKind::Caption };
+        let npz = Sidecar {
+            path: PathBuf::from("dir/foo_1024x1024_sdxl.npz"),
+            kind: SidecarKind::Latents,
+        };
+        assert_eq!(sample.renamed_sidecar(&txt, "bar"), PathBuf::from("dir/bar.png.txt"));
+        assert_eq!(
+            sample.renamed_sidecar(&npz, "bar"),
+            PathBuf::from("dir/bar_1024x1024_sdxl.npz")
+        );
+    }
+}
@@ -0,0 +1,149 @@
+// src/dtext.rs
+
+// DText to plain sentences
+//
+// e621 writes post descriptions in DText, its own markup: `[b]bold[/b]` and other BBCode-like
+// tags, `"text":https://...` links, `[[wiki page|label]]` and `{{tag search}}` links, `post #123`
+// references, `h1.` headers and `*` list items. Captions want none of it, so `to_sentences`
+// keeps the readable text: link labels stay, URLs, searches and references go, and quotes and
+// code blocks are dropped with their content, as they are rarely about the image.
+//
+// Every line becomes a sentence, so headers and list items end with a period, and whitespace
+// is collapsed into single spaces. Descriptions can be pages long, so the text is cut to whole
+// sentences that fit a maximum length, and a first sentence that is too long on its own leaves
+// nothing rather than half a sentence.
+
+use std::sync::LazyLock;
+use regex::Regex;
+
+/// Blocks dropped with their content.
+static BLOCK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)\[(quote|code)\].*?\[/(quote|code)\]").unwrap()
+});
+
+/// `"label":url` and `"label":[url]` links. URLs do not end in punctuation, which belongs to
+/// the sentence.
+static LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#""([^"\n]+)":(?:\[[^\]\n]*\]|\S*[^\s.,!?;:)])"#).unwrap()
+});
+
+/// `[[page]]` and `[[page|label]]` wiki links.
+static WIKI_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[\[([^\]|]+)(?:\|([^\]]*))?\]\]").unwrap()
+});
+
+/// `{{search}}` links, bare and bracketed URLs, and references such as `post #123`.
+static REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        concat!(
+            r"(?i)\{\{[^}]*\}\}|<?https?://[^\s>]*[^\s>.,!?;:)]>?",
+            r"|\b(?:post|pool|set|comment|forum|topic|user|artist|wiki|note)\s*#\d+"
+        )
+    ).unwrap()
+});
+
+/// BBCode-like tags such as `[b]`, `[/b]`, `[color=red]` and `[section,expanded=Title]`.
+static MARKUP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\[/?[a-z][a-z0-9]*(?:[=,][^\]\n]*)?\]").unwrap()
+});
+
+/// Header and list item markers at the start of a line.
+static LINE_MARKER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:h[1-6](?:#[\w-]+)?\.|\*+\s)\s*").unwrap()
+});
+
+/// Parentheses left empty by removed links.
+static EMPTY_PARENTHESES: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\(\s*\)|\[\s*\]").unwrap()
+});
+
+/// Whitespace left in front of punctuation by removed links.
+static SPACE_BEFORE_PUNCTUATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\s+([.,!?;:])").unwrap()
+});
+
+/// Converts `DText` to plain sentences of at most `max_len` characters.
+///
+/// # Returns
+///
+/// * `String` - The sentences, or an empty string if no text is left or the first sentence is
+///   longer than `max_len`.
+#[must_use = "Returns the sentences and the result should be used"]
+pub fn to_sentences(dtext: &str, max_len: usize) -> String {
+    let text = BLOCK.replace_all(dtext, "");
+    let text = LINK.replace_all(&text, "$1");
+    let text = WIKI_LINK.replace_all(&text, |captures: &regex::Captures<'_>| {
+        let label = captures.get(2).map_or("", |label| label.as_str().trim());
+        let label = if label.is_empty() { &captures[1] } else { label };
+        label.replace('_', " ")
+    });
+    let text = REFERENCE.replace_all(&text, "");
+    let text = MARKUP.replace_all(&text, "");
+
+    let mut sentences = Vec::new();
+    for line in text.lines() {
+        let line = LINE_MARKER.replace(line, "");
+        let line = EMPTY_PARENTHESES.replace_all(&line, "");
+        let line = SPACE_BEFORE_PUNCTUATION.replace_all(&line, "$1");
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        let line = line.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '"');
+        if !line.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+        let line = line.trim_end_matches([',', ';', ':', '-']);
+        if line.trim_end_matches(['"', '\'', ')']).ends_with(['.', '!', '?']) {
+            sentences.push(line.to_string());
+        } else {
+            sentences.push(format!("{line}."));
+        }
+    }
+    truncate_sentences(&sentences.join(" "), max_len)
+}
+
+/// Cuts text to the whole sentences that fit in `max_len` characters.
+fn truncate_sentences(text: &str, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        return text.to_string();
+    }
+    let mut end = 0;
+    let mut chars = text.char_indices().enumerate().peekable();
+    while let Some((count, (i, c))) = chars.next() {
+        if count >= max_len {
+            break;
+        }
+        let ends_sentence = matches!(c, '.' | '!' | '?') &&
+            chars.peek().is_some_and(|(_, (_, next))| next.is_whitespace());
+        if ends_sentence {
+            end = i + c.len_utf8();
+        }
+    }
+    text[..end].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_sentences() {
+        let dtext = "h4. Commission\r\n\
+            [b]Krystal[/b] relaxing at the \"beach\":https://example.com/beach ([[krystal]])\r\n\
+            [quote]someone said: nice[/quote]\r\n\
+            * Sketch by [[someone_else|Someone Else]] (post #1234)\r\n\
+            Alternate versions: {{krystal rating:s}}\r\n\
+            [section=Links]Patreon: https://patreon.com/someone[/section]";
+        assert_eq!(
+            to_sentences(dtext, 500),
+            "Commission. Krystal relaxing at the beach (krystal). \
+            Sketch by Someone Else. Alternate versions. Patreon."
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        let text = "A fox sits. It is late! The moon is up.";
+        assert_eq!(to_sentences(text, 100), text);
+        assert_eq!(to_sentences(text, 25), "A fox sits. It is late!");
+        assert_eq!(to_sentences(text, 5), "");
+    }
+}
@@ -0,0 +1,414 @@
+// src/e621.rs
+
+// e621 database exports
+//
+// e621 publishes daily CSV dumps of its database at https://e621.net/db_export/. Two of them
+// describe how tags relate: `tag_aliases` maps an old name to the tag it was merged into, and
+// `tag_implications` says that one tag implies another, such as `wolf` implying `canine`.
+// Both list the tags as `antecedent_name` and `consequent_name`, and only rows whose `status`
+// is `active` apply.
+//
+// `TagRelations` loads the downloaded files, so posts and captions can be resolved offline:
+// aliased tags are renamed to their canonical tag, and tags implied by another tag of the same
+// caption can be dropped. The exports use underscores in tag names, while captions use spaces
+// and may escape parentheses; caption tags are compared in the e621 form and written back in
+// the style they were found in.
+//
+// The `posts` export holds every post with its tags as one space-separated `tag_string`, and
+// the `tags` export gives the category of each tag as a number. `read_posts` streams the posts
+// row by row, since the file is several gigabytes, and `ExportPost::to_post` groups the tags
+// with `TagCategories` into a `BooruPost`, so it converts like a post from the e621 API.
+//
+// `CaptionStyle` selects how converted captions spell the rating of a post and whether they
+// rank it with a quality tag, as base models were trained with different conventions.
+
+use std::{ collections::{ BTreeMap, HashMap, HashSet }, fs::File, io::Read, path::Path };
+use anyhow::{ Context, Result };
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{ unescape_tag, BooruPost, Caption, Rating };
+
+/// How converted captions write the rating of a post and its quality.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionStyle {
+    /// `safe`, `questionable` or `nsfw`, without a quality tag
+    #[default]
+    Plain,
+    /// `score_9` to `score_4` followed by `rating_safe`, `rating_questionable` or
+    /// `rating_explicit`, for Pony Diffusion and similar SDXL models
+    Pony,
+    /// The `rating-template` and `score-template` of the config
+    Custom,
+}
+
+/// A row of the `tag_aliases` or `tag_implications` export.
+#[derive(Debug, Deserialize)]
+struct Relation {
+    antecedent_name: String,
+    consequent_name: String,
+    status: String,
+}
+
+/// Tag aliases and implications from the e621 database exports.
+#[derive(Debug, Clone, Default)]
+pub struct TagRelations {
+    /// Old tag names and the tag they are an alias of.
+    aliases: HashMap<String, String>,
+    /// Tags and the tags they directly imply.
+    implications: HashMap<String, Vec<String>>,
+}
+
+impl TagRelations {
+    /// Loads the aliases and implications from the given export files, either of which can be
+    /// left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read or is not a valid export.
+    pub fn load(aliases: Option<&Path>, implications: Option<&Path>) -> Result<Self> {
+        let mut relations = Self::default();
+        if let Some(path) = aliases {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            relations.read_aliases(file)
+                .with_context(|| format!("Invalid tag aliases {}", path.display()))?;
+        }
+        if let Some(path) = implications {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            relations.read_implications(file)
+                .with_context(|| format!("Invalid tag implications {}", path.display()))?;
+        }
+        Ok(relations)
+    }
+
+    /// Adds the active aliases of a `tag_aliases` export.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CSV cannot be read or lacks the expected columns.
+    pub fn read_aliases(&mut self, reader: impl Read) -> Result<()> {
+        for relation in active_relations(reader) {
+            let relation = relation?;
+            self.aliases.insert(relation.antecedent_name, relation.consequent_name);
+        }
+        Ok(())
+    }
+
+    /// Adds the active implications of a `tag_implications` export.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CSV cannot be read or lacks the expected columns.
+    pub fn read_implications(&mut self, reader: impl Read) -> Result<()> {
+        for relation in active_relations(reader) {
+            let relation = relation?;
+            self.implications
+                .entry(relation.antecedent_name)
+                .or_default()
+                .push(relation.consequent_name);
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if no aliases or implications are loaded.
+    #[must_use = "Determines if any relations are loaded and the result should be checked"]
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty() && self.implications.is_empty()
+    }
+
+    /// Returns the canonical name of an e621 tag, following chains of aliases.
+    #[must_use = "Returns the canonical tag and the result should be used"]
+    pub fn canonical<'a>(&'a self, name: &'a str) -> &'a str {
+        let mut name = name;
+        // Bounded, in case the export ever contains a cycle
+        for _ in 0..=self.aliases.len() {
+            match self.aliases.get(name) {
+                Some(consequent) if consequent != name => {
+                    name = consequent;
+                }
+                _ => {
+                    break;
+                }
+            }
+        }
+        name
+    }
+
+    /// Returns every tag implied by the given canonical e621 tags, directly or through other
+    /// implications.
+    #[must_use = "Returns the implied tags and the result should be used"]
+    pub fn implied<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> HashSet<String> {
+        let mut implied = HashSet::new();
+        let mut pending: Vec<&str> = names.into_iter().collect();
+        while let Some(name) = pending.pop() {
+            for consequent in self.implications.get(name).into_iter().flatten() {
+                let consequent = self.canonical(consequent);
+                if implied.insert(consequent.to_string()) {
+                    pending.push(consequent);
+                }
+            }
+        }
+        implied
+    }
+
+    /// Renames the aliased e621 tags to their canonical names and removes the duplicates this
+    /// creates. With `drop_implied`, tags implied by another of the tags are removed too.
+    #[must_use = "Returns the resolved tags and the result should be used"]
+    pub fn resolve<S: AsRef<str>>(&self, names: &[S], drop_implied: bool) -> Vec<String> {
+        let canonical: Vec<&str> = names.iter().map(|name| self.canonical(name.as_ref())).collect();
+        let implied = if drop_implied {
+            self.implied(canonical.iter().copied())
+        } else {
+            HashSet::new()
+        };
+
+        let mut seen = HashSet::new();
+        canonical
+            .into_iter()
+            .filter(|name| !implied.contains(*name) && seen.insert(*name))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Resolves the tags of a caption like [`TagRelations::resolve`]. Keep tokens and
+    /// sentences are left alone.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the tags changed.
+    pub fn resolve_caption(&self, caption: &mut Caption, drop_implied: bool) -> bool {
+        let names: Vec<String> = caption.tags.iter().map(|tag| e621_name(tag)).collect();
+        let resolved = self.resolve(&names, drop_implied);
+
+        let tags: Vec<String> = resolved
+            .iter()
+            .map(|name| {
+                // Tags that did not change keep their exact spelling
+                match names.iter().position(|original| original == name) {
+                    Some(i) => caption.tags[i].clone(),
+                    None => caption.styled_tag(name),
+                }
+            })
+            .collect();
+
+        let changed = tags != caption.tags;
+        caption.tags = tags;
+        changed
+    }
+}
+
+/// The e621 tag categories, indexed by the number the `tags` export uses.
+const CATEGORY_NAMES: [&str; 9] = [
+    "general",
+    "artist",
+    "contributor",
+    "copyright",
+    "character",
+    "species",
+    "invalid",
+    "meta",
+    "lore",
+];
+
+/// A row of the `tags` export.
+#[derive(Debug, Deserialize)]
+struct TagRow {
+    name: String,
+    category: usize,
+}
+
+/// The category of every tag, from the `tags` export of the e621 database.
+#[derive(Debug, Clone, Default)]
+pub struct TagCategories {
+    categories: HashMap<String, &'static str>,
+}
+
+impl TagCategories {
+    /// Loads the categories from a `tags` export file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid export.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut categories = Self::default();
+        categories.read(file).with_context(|| format!("Invalid tags {}", path.display()))?;
+        Ok(categories)
+    }
+
+    /// Adds the categories of a `tags` export. General tags are not stored, as unknown tags
+    /// are general anyway.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CSV cannot be read or lacks the expected columns.
+    pub fn read(&mut self, reader: impl Read) -> Result<()> {
+        for row in csv::Reader::from_reader(reader).into_deserialize::<TagRow>() {
+            let row = row?;
+            if let Some(&category) = CATEGORY_NAMES.get(row.category).filter(|&&c| c != "general") {
+                self.categories.insert(row.name, category);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the category of an e621 tag, `general` if it is unknown.
+    #[must_use = "Returns the category of the tag and the result should be used"]
+    pub fn category(&self, name: &str) -> &'static str {
+        self.categories.get(name).copied().unwrap_or(CATEGORY_NAMES[0])
+    }
+}
+
+/// A row of the `posts` export, with the columns the converters use.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportPost {
+    pub id: u64,
+    pub md5: String,
+    pub rating: String,
+    /// The tags of the post, separated by spaces.
+    pub tag_string: String,
+    pub fav_count: i64,
+    pub score: i64,
+    pub file_ext: String,
+    /// The description of the post in `DText`.
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ExportPost {
+    /// Returns the post with its tags grouped by category, so it converts like a post from the
+    /// e621 API. The images of the export are named by their MD5.
+    #[must_use = "Returns the post and the result should be used"]
+    pub fn to_post(&self, categories: &TagCategories) -> BooruPost {
+        let mut tags: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for tag in self.tag_string.split_whitespace() {
+            tags.entry(categories.category(tag).to_string()).or_default().push(tag.to_string());
+        }
+        let md5 = self.md5.to_lowercase();
+        BooruPost {
+            id: Some(self.id),
+            file_stem: Some(md5.clone()),
+            md5: Some(md5),
+            rating: Rating::parse(&self.rating),
+            score: self.score,
+            favorites: self.fav_count,
+            tags,
+            description: Some(self.description.clone()).filter(|text| !text.is_empty()),
+            pools: Vec::new(),
+        }
+    }
+}
+
+/// Streams the rows of a `posts` export one at a time, so the file is never held in memory.
+pub fn read_posts(reader: impl Read) -> impl Iterator<Item = Result<ExportPost>> {
+    csv::Reader::from_reader(reader)
+        .into_deserialize::<ExportPost>()
+        .map(|row| row.map_err(anyhow::Error::from))
+}
+
+/// Reads the rows of an export whose status is `active`.
+fn active_relations(reader: impl Read) -> impl Iterator<Item = Result<Relation>> {
+    csv::Reader::from_reader(reader)
+        .into_deserialize::<Relation>()
+        .map(|row| row.map_err(anyhow::Error::from))
+        .filter(|row| row.as_ref().map_or(true, |relation| relation.status == "active"))
+}
+
+/// Returns the e621 name of a caption tag: unescaped, lowercase and with underscores.
+#[must_use = "Returns the e621 tag name and the result should be used"]
+pub fn e621_name(tag: &str) -> String {
+    unescape_tag(tag).trim().to_lowercase().replace(' ', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALIASES: &str = "id,antecedent_name,consequent_name,created_at,status
+1,wolfie,wolf,2020-01-01,active
+2,grey_wolf,gray_wolf,2020-01-01,active
+3,gray_wolf,wolf,2020-01-01,active
+4,doggo,dog,2020-01-01,deleted
+";
+
+    const IMPLICATIONS: &str = "id,antecedent_name,consequent_name,created_at,status
+1,wolf,canine,2020-01-01,active
+2,canine,canid,2020-01-01,active
+3,canid,mammal,2020-01-01,active
+4,dog,canine,2020-01-01,pending
+";
+
+    fn relations() -> TagRelations {
+        let mut relations = TagRelations::default();
+        relations.read_aliases(ALIASES.as_bytes()).unwrap();
+        relations.read_implications(IMPLICATIONS.as_bytes()).unwrap();
+        relations
+    }
+
+    #[test]
+    fn test_canonical() {
+        let relations = relations();
+        assert_eq!(relations.canonical("grey_wolf"), "wolf");
+        assert_eq!(relations.canonical("wolf"), "wolf");
+        // Only active rows apply
+        assert_eq!(relations.canonical("doggo"), "doggo");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let relations = relations();
+        let tags = ["wolfie", "canine", "wolf", "solo", "mammal"];
+        assert_eq!(relations.resolve(&tags, false), ["wolf", "canine", "solo", "mammal"]);
+        assert_eq!(relations.resolve(&tags, true), ["wolf", "solo"]);
+        assert_eq!(relations.resolve(&["dog", "canine"], true), ["dog", "canine"]);
+    }
+
+    #[test]
+    fn test_read_posts() {
+        let tags = "id,name,category,post_count
+1,someone,1,10
+2,red_fox,5,100
+3,fur,0,1000
+4,zootopia,3,50
+";
+        let posts = "id,md5,rating,tag_string,fav_count,score,file_ext,description
+42,0123abcd,e,fur red_fox someone unknown_tag zootopia,7,12,png,\"A fox, probably.\"
+";
+        let mut categories = TagCategories::default();
+        categories.read(tags.as_bytes()).unwrap();
+        assert_eq!(categories.category("red_fox"), "species");
+        assert_eq!(categories.category("unknown_tag"), "general");
+
+        let posts: Vec<ExportPost> = read_posts(posts.as_bytes()).collect::<Result<_>>().unwrap();
+        assert_eq!(posts.len(), 1);
+        let post = posts[0].to_post(&categories);
+        assert_eq!((post.rating, post.score, post.favorites), (Rating::Explicit, 12, 7));
+        assert_eq!(post.md5.as_deref(), Some("0123abcd"));
+        assert_eq!(post.description.as_deref(), Some("A fox, probably."));
+        assert_eq!(
+            post.tags,
+            BTreeMap::from([
+                ("artist".to_string(), vec!["someone".to_string()]),
+                ("copyright".to_string(), vec!["zootopia".to_string()]),
+                ("general".to_string(), vec!["fur".to_string(), "unknown_tag".to_string()]),
+                ("species".to_string(), vec!["red_fox".to_string()]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_resolve_caption() {
+        let relations = relations();
+        let mut caption = Caption::parse("feral ||| grey wolf, canine, solo, A wolf howls.");
+        assert!(relations.resolve_caption(&mut caption, true));
+        assert_eq!(caption.to_string(), "feral ||| wolf, solo, A wolf howls.");
+        assert!(!relations.resolve_caption(&mut caption, true));
+
+        let mut caption = Caption::parse("grey_wolf, looking_at_viewer");
+        relations.resolve_caption(&mut caption, false);
+        assert_eq!(caption.to_string(), "wolf, looking_at_viewer");
+    }
+}
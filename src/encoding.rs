@@ -0,0 +1,120 @@
+// src/encoding.rs
+
+// Text encoding detection
+//
+// Captions edited with Windows tools are not always UTF-8: some are saved as UTF-16 or in the
+// Windows-1252 code page, often with a byte order mark and CRLF line endings. Every text read
+// in the library goes through `decode_text`, which sniffs the BOM, recognizes UTF-16 without
+// one, falls back to Windows-1252 for anything that is not valid UTF-8, and hands the caller
+// BOM-free text with LF line endings.
+
+use encoding_rs::{ Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252 };
+
+/// Text decoded from a file, along with what was found in the raw bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedText {
+    /// The decoded text without BOM and with LF line endings.
+    pub text: String,
+    /// The encoding the bytes were decoded from.
+    pub encoding: &'static Encoding,
+    /// Whether the bytes started with a byte order mark.
+    pub had_bom: bool,
+    /// Whether the text contained CRLF or lone CR line endings.
+    pub had_crlf: bool,
+}
+
+impl DecodedText {
+    /// Returns `true` if the file already is UTF-8 without BOM and uses LF line endings.
+    #[must_use = "Determines if the file needs to be rewritten and the result should be checked"]
+    pub fn is_normalized(&self) -> bool {
+        self.encoding == UTF_8 && !self.had_bom && !self.had_crlf
+    }
+}
+
+/// Guesses the encoding of UTF-16 text without a BOM from where its zero bytes are.
+///
+/// Mostly-ASCII UTF-16 has a zero in every other byte, which never happens in UTF-8 or
+/// Windows-1252 text.
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let even_zeros = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_zeros = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+
+    if odd_zeros * 2 > pairs && even_zeros == 0 {
+        Some(UTF_16LE)
+    } else if even_zeros * 2 > pairs && odd_zeros == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Converts CRLF and lone CR line endings to LF.
+#[must_use = "Returns the text with normalized line endings and the result should be used"]
+pub fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Decodes the raw bytes of a text file, detecting its encoding.
+///
+/// A BOM decides the encoding when present. Otherwise UTF-16 is recognized by its zero
+/// bytes, valid UTF-8 is taken as is, and anything else is decoded as Windows-1252.
+#[must_use = "Decodes the bytes of a text file and the result should be used"]
+pub fn decode_text(bytes: &[u8]) -> DecodedText {
+    let (encoding, bom_length) = Encoding::for_bom(bytes).unwrap_or_else(|| {
+        let encoding = sniff_utf16(bytes).unwrap_or_else(|| {
+            if std::str::from_utf8(bytes).is_ok() { UTF_8 } else { WINDOWS_1252 }
+        });
+        (encoding, 0)
+    });
+
+    let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+    let had_crlf = text.contains('\r');
+    let text = if had_crlf { normalize_line_endings(&text) } else { text.into_owned() };
+
+    DecodedText { text, encoding, had_bom: bom_length > 0, had_crlf }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf8() {
+        let decoded = decode_text("tag, other tag\n".as_bytes());
+        assert_eq!(decoded.text, "tag, other tag\n");
+        assert!(decoded.is_normalized());
+    }
+
+    #[test]
+    fn test_decode_utf8_bom_crlf() {
+        let decoded = decode_text(b"\xEF\xBB\xBFtag, \xC3\xA9\r\nsentence\r");
+        assert_eq!(decoded.text, "tag, \u{e9}\nsentence\n");
+        assert_eq!(decoded.encoding, UTF_8);
+        assert!(decoded.had_bom && decoded.had_crlf);
+    }
+
+    #[test]
+    fn test_decode_utf16() {
+        let le: Vec<u8> = "tag, \u{e9}".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = "tag, \u{e9}".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let mut le_bom = vec![0xFF, 0xFE];
+        le_bom.extend(&le);
+
+        for (bytes, encoding) in [(le_bom, UTF_16LE), (le, UTF_16LE), (be, UTF_16BE)] {
+            let decoded = decode_text(&bytes);
+            assert_eq!(decoded.text, "tag, \u{e9}");
+            assert_eq!(decoded.encoding, encoding);
+        }
+    }
+
+    #[test]
+    fn test_decode_windows_1252() {
+        let decoded = decode_text(b"caf\xE9, \x93quoted\x94");
+        assert_eq!(decoded.text, "caf\u{e9}, \u{201c}quoted\u{201d}");
+        assert_eq!(decoded.encoding, WINDOWS_1252);
+    }
+}
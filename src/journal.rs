@@ -0,0 +1,352 @@
+// src/journal.rs
+
+// Change journal
+//
+// Every library helper that mutates a dataset (`write_to_file`, `write_bytes_to_file`,
+// `rename_file` and `remove_file`) goes through this module. A run can either be previewed
+// with `--dry-run`, which prints a diff of every change instead of applying it, or recorded
+// with `--journal <dir>`, which keeps the original bytes and renames in `<dir>` so the run can
+// be reverted later with `undo <dir>`.
+//
+// A journal directory contains `journal.jsonl`, one JSON object per change in the order they
+// were applied, and a `blobs` folder holding the original content of every overwritten or
+// removed file.
+//
+// Changes are recorded before they are applied, so a change that failed can be in the journal
+// without having happened; undo treats a file it should remove that is already gone as
+// reverted. Once every change is reverted, `journal.jsonl` is renamed to
+// `journal.jsonl.undone`, so a second undo cannot apply the renames again. If a change cannot
+// be reverted, undo stops there and leaves only the changes that are not reverted yet in
+// `journal.jsonl`, so it can be run again once the problem is fixed.
+
+use std::{
+    io::{ IsTerminal, Write },
+    path::{ Path, PathBuf },
+    sync::{ atomic::{ AtomicBool, AtomicUsize, Ordering }, Mutex, OnceLock },
+};
+use anyhow::{ bail, Context, Result };
+use crossterm::style::Stylize;
+use serde_json::{ json, Value };
+use similar::TextDiff;
+use tokio::{ fs, io };
+
+const LOG_FILE: &str = "journal.jsonl";
+const UNDONE_FILE: &str = "journal.jsonl.undone";
+const BLOB_DIR: &str = "blobs";
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static JOURNAL: OnceLock<Journal> = OnceLock::new();
+
+struct Journal {
+    dir: PathBuf,
+    log: Mutex<std::fs::File>,
+    next_blob: AtomicUsize,
+}
+
+/// Configures the journal for the current process.
+///
+/// With `dry_run` set, mutating helpers only print what they would do. With a `journal_dir`,
+/// every change is recorded there so it can be reverted with [`undo`].
+///
+/// # Errors
+///
+/// Returns an error if the journal directory already contains a journal or cannot be created,
+/// or if the journal was already configured.
+pub fn init(dry_run: bool, journal_dir: Option<&Path>) -> Result<()> {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+
+    let Some(dir) = journal_dir else {
+        return Ok(());
+    };
+    if dry_run {
+        // Nothing will change, so there is nothing to record
+        return Ok(());
+    }
+    if dir.join(LOG_FILE).exists() {
+        bail!("{} already contains a journal", dir.display());
+    }
+    std::fs::create_dir_all(dir.join(BLOB_DIR))
+        .with_context(|| format!("Failed to create journal directory {}", dir.display()))?;
+    let log = std::fs::File::create(dir.join(LOG_FILE)).context("Failed to create journal")?;
+    let dir = dir.canonicalize().context("Failed to canonicalize journal directory")?;
+
+    JOURNAL.set(Journal { dir, log: Mutex::new(log), next_blob: AtomicUsize::new(0) }).map_err(
+        |_| anyhow::anyhow!("Journal is already configured")
+    )
+}
+
+/// Returns `true` if changes should only be previewed.
+#[must_use = "Determines if changes should be applied and the result should be checked"]
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Prints a unified diff of the changes to a file, such as one that would be rewritten during a
+/// dry run. The new version is named after the file followed by `label` in parentheses. The
+/// diff is coloured when stdout is a terminal.
+pub fn print_diff(path: &Path, label: &str, old: &str, new: &str) {
+    let old_header = path.display().to_string();
+    let new_header = format!("{old_header} ({label})");
+    let diff = TextDiff::from_lines(old, new);
+    let diff = diff.unified_diff().header(&old_header, &new_header).to_string();
+    if !std::io::stdout().is_terminal() {
+        print!("{diff}");
+        return;
+    }
+
+    let mut colored = String::with_capacity(diff.len());
+    for line in diff.lines() {
+        let line = if line.starts_with("---") || line.starts_with("+++") {
+            line.bold().to_string()
+        } else if line.starts_with("@@") {
+            line.cyan().to_string()
+        } else if line.starts_with('+') {
+            line.green().to_string()
+        } else if line.starts_with('-') {
+            line.red().to_string()
+        } else {
+            line.to_string()
+        };
+        colored.push_str(&line);
+        colored.push('\n');
+    }
+    // Printed at once, so diffs of files processed concurrently do not interleave
+    print!("{colored}");
+}
+
+fn append(entry: &Value) -> io::Result<()> {
+    let Some(journal) = JOURNAL.get() else {
+        return Ok(());
+    };
+    let mut log = journal.log.lock().map_err(|_| io::Error::other("Journal lock poisoned"))?;
+    writeln!(log, "{entry}")?;
+    log.flush()
+}
+
+async fn save_blob(path: &Path) -> io::Result<Option<String>> {
+    let Some(journal) = JOURNAL.get() else {
+        return Ok(None);
+    };
+    let original = match fs::read(path).await {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+    let name = format!("{:06}", journal.next_blob.fetch_add(1, Ordering::Relaxed));
+    fs::write(journal.dir.join(BLOB_DIR).join(&name), original).await?;
+    Ok(Some(name))
+}
+
+/// Records that `path` is about to be overwritten or created.
+pub(crate) async fn record_write(path: &Path) -> io::Result<()> {
+    if JOURNAL.get().is_none() {
+        return Ok(());
+    }
+    let blob = save_blob(path).await?;
+    append(&json!({ "op": "write", "path": std::path::absolute(path)?, "blob": blob }))
+}
+
+/// Records that `from` is about to be renamed to `to`.
+pub(crate) async fn record_rename(from: &Path, to: &Path) -> io::Result<()> {
+    if JOURNAL.get().is_none() {
+        return Ok(());
+    }
+    // Renaming over an existing file replaces it, so keep its content too
+    let blob = save_blob(to).await?;
+    let from = std::path::absolute(from)?;
+    let to = std::path::absolute(to)?;
+    append(&json!({ "op": "rename", "from": from, "to": to, "blob": blob }))
+}
+
+/// Records that `path` is about to be removed.
+pub(crate) async fn record_remove(path: &Path) -> io::Result<()> {
+    if JOURNAL.get().is_none() {
+        return Ok(());
+    }
+    let blob = save_blob(path).await?;
+    append(&json!({ "op": "remove", "path": std::path::absolute(path)?, "blob": blob }))
+}
+
+fn entry_path(entry: &Value, key: &str) -> Result<PathBuf> {
+    entry
+        .get(key)
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .with_context(|| format!("Journal entry is missing `{key}`: {entry}"))
+}
+
+/// Restores the original content of `path` from a blob, or removes `path` if it did not exist
+/// before the change.
+async fn restore(journal_dir: &Path, blob: Option<&str>, path: &Path) -> Result<()> {
+    let dry_run = is_dry_run();
+    if let Some(blob) = blob {
+        println!("Restoring: {}", path.display());
+        if !dry_run {
+            fs::copy(journal_dir.join(BLOB_DIR).join(blob), path).await.with_context(|| {
+                format!("Failed to restore {}", path.display())
+            })?;
+        }
+        return Ok(());
+    }
+    println!("Removing: {}", path.display());
+    if dry_run {
+        return Ok(());
+    }
+    match fs::remove_file(path).await {
+        // The change was recorded but never happened, such as a create that failed
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result.with_context(|| format!("Failed to remove {}", path.display())),
+    }
+}
+
+/// Reverts one recorded change.
+async fn revert(journal_dir: &Path, entry: &Value) -> Result<()> {
+    let blob = entry.get("blob").and_then(Value::as_str);
+    match entry.get("op").and_then(Value::as_str) {
+        Some("write") => restore(journal_dir, blob, &entry_path(entry, "path")?).await,
+        Some("rename") => {
+            let from = entry_path(entry, "from")?;
+            let to = entry_path(entry, "to")?;
+            println!("Renaming: {} -> {}", to.display(), from.display());
+            if !is_dry_run() {
+                match fs::rename(&to, &from).await {
+                    // The rename was recorded but never happened
+                    Err(e) if e.kind() == io::ErrorKind::NotFound && from.exists() => {
+                        return Ok(());
+                    }
+                    result => result.with_context(|| {
+                        format!("Failed to rename {} back to {}", to.display(), from.display())
+                    })?,
+                }
+            }
+            if blob.is_some() {
+                restore(journal_dir, blob, &to).await?;
+            }
+            Ok(())
+        }
+        Some("remove") if blob.is_some() => {
+            restore(journal_dir, blob, &entry_path(entry, "path")?).await
+        }
+        Some("remove") => Ok(()),
+        _ => bail!("Unknown journal entry: {entry}"),
+    }
+}
+
+/// Reverts every change recorded in a journal, newest first, and marks the journal as undone.
+///
+/// Honours dry-run mode, in which the changes that would be reverted are only printed and the
+/// journal is left as it is.
+///
+/// # Errors
+///
+/// Returns an error if the journal cannot be read or parsed, was already undone, or a change
+/// cannot be reverted. In the last case, the journal keeps the changes that are not reverted
+/// yet, so undo can be run again.
+pub async fn undo(journal_dir: &Path) -> Result<()> {
+    let log_path = journal_dir.join(LOG_FILE);
+    if !log_path.exists() && journal_dir.join(UNDONE_FILE).exists() {
+        bail!("The journal in {} was already undone", journal_dir.display());
+    }
+    let log = fs
+        ::read_to_string(&log_path).await
+        .with_context(|| format!("Failed to read journal in {}", journal_dir.display()))?;
+    let lines: Vec<&str> = log.lines().filter(|line| !line.trim().is_empty()).collect();
+    let entries = lines
+        .iter()
+        .map(|line| serde_json::from_str(line))
+        .collect::<Result<Vec<Value>, _>>()
+        .context("Failed to parse journal")?;
+
+    for (i, entry) in entries.iter().enumerate().rev() {
+        if let Err(e) = revert(journal_dir, entry).await {
+            eprintln!("Failed: {e:#}");
+            if !is_dry_run() {
+                // The failed change and everything before it are still applied
+                let mut remaining = lines[..=i].join("\n");
+                remaining.push('\n');
+                fs::write(&log_path, remaining).await.context("Failed to update journal")?;
+            }
+            bail!(
+                "{} of {} changes were not reverted, run undo again once the problem is fixed",
+                i + 1,
+                entries.len()
+            );
+        }
+    }
+
+    if !is_dry_run() {
+        fs::rename(&log_path, journal_dir.join(UNDONE_FILE)).await.context(
+            "Failed to mark the journal as undone"
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Writes a journal of the given entries, with their blobs, as a run would have.
+    fn write_journal(dir: &Path, entries: &[Value], blobs: &[(&str, &str)]) {
+        std::fs::create_dir_all(dir.join(BLOB_DIR)).unwrap();
+        for (name, content) in blobs {
+            std::fs::write(dir.join(BLOB_DIR).join(name), content).unwrap();
+        }
+        let log: Vec<String> = entries.iter().map(Value::to_string).collect();
+        std::fs::write(dir.join(LOG_FILE), log.join("\n") + "\n").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undo_skips_changes_that_never_happened() {
+        let data = TempDir::new().unwrap();
+        let journal = TempDir::new().unwrap();
+        let (created, written) = (data.path().join("a.txt"), data.path().join("b.txt"));
+        let (from, to) = (data.path().join("c.png"), data.path().join("d.png"));
+        std::fs::write(&written, "new").unwrap();
+        std::fs::write(&from, "image").unwrap();
+        write_journal(
+            journal.path(),
+            &[
+                // The create and the rename were recorded, but failed
+                json!({ "op": "write", "path": created, "blob": null }),
+                json!({ "op": "write", "path": written, "blob": "000000" }),
+                json!({ "op": "rename", "from": from, "to": to, "blob": null }),
+            ],
+            &[("000000", "old")]
+        );
+
+        undo(journal.path()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&written).unwrap(), "old");
+        assert_eq!(std::fs::read_to_string(&from).unwrap(), "image");
+        assert!(!created.exists());
+
+        // The journal is marked as undone, so it cannot be applied twice
+        assert!(journal.path().join(UNDONE_FILE).exists());
+        assert!(undo(journal.path()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_undo_keeps_the_changes_it_could_not_revert() {
+        let data = TempDir::new().unwrap();
+        let journal = TempDir::new().unwrap();
+        let (removed, created) = (data.path().join("a.txt"), data.path().join("b.txt"));
+        std::fs::write(&created, "new").unwrap();
+        let entries = [
+            // Its blob is missing, so it cannot be restored
+            json!({ "op": "remove", "path": removed, "blob": "000000" }),
+            json!({ "op": "write", "path": created, "blob": null }),
+        ];
+        write_journal(journal.path(), &entries, &[]);
+
+        assert!(undo(journal.path()).await.is_err());
+        assert!(!created.exists());
+        let log = std::fs::read_to_string(journal.path().join(LOG_FILE)).unwrap();
+        assert_eq!(log, format!("{}\n", entries[0]));
+    }
+}
+
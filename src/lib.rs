@@ -9,13 +9,28 @@
 // - Checking files for multiple lines and opening them in Neovim
 // - Formatting text content by replacing multiple spaces with a single space
 // - Detecting and skipping hidden directories, Git directories, and build output directories during directory walks
+// - Excluding paths from every walk with `.datasetignore` files and `--exclude` globs
 // - Processing Rust files and checking for required compiler warnings
 // - Reading and writing JSON files, including formatting and extracting metadata from SafeTensors files
-// - Determining if a file is an image and checking if a caption file exists and is not empty
+// - Determining if a file is an image
+// - Scanning a dataset directory into images paired with their caption, JSON and latent sidecars
 // - Renaming image files to remove the extension
 // - Converting JSON files to caption files
 // - Deleting files with a specific extension in a directory and its subdirectories
 // - Removing letterboxing from image files
+// - Walking directories with bounded concurrency and collecting per-file errors into a summary
+// - Previewing changes with `--dry-run` and recording them to a journal that can be undone
+// - Replacing files atomically, optionally keeping `.bak` backups and modification times
+// - Reading text files in any common encoding as UTF-8 with LF line endings
+// - Sharing the `--dir`, `--dry-run`, `--jobs` and `--verbose` flags between every tool
+// - Reading per-dataset settings from the nearest `dataset-tools.toml`
+// - Parsing captions into keep tokens, escape-aware tags and sentences that print back exactly
+// - Reading post metadata of e621, Danbooru, Gelbooru and Rule34 into one post record
+// - Cleaning e621 DText descriptions into plain sentences for captions
+// - Resolving e621 tag aliases and implications offline from the database exports
+// - Filtering tags with rule files of literal tags, globs and regexes, as a blacklist or whitelist
+// - Hashing files with MD5 to match samples by content
+// - Counting CLIP tokens offline with a byte-level BPE tokenizer loaded from a merges file
 //
 // This library is designed to be a useful set of tools for working with a variety of data types and formats,
 // simplifying common data processing tasks and helping to maintain code quality and consistency.
@@ -47,7 +62,7 @@
 
 use std::{ sync::Arc, path::{ Path, PathBuf } };
 use log::{ info, warn };
-use walkdir::{ DirEntry, WalkDir };
+use walkdir::DirEntry;
 use serde_json::{ Value, Map };
 use anyhow::{ Context, Result };
 use memmap2::Mmap;
@@ -56,13 +71,54 @@ use image::{ GenericImageView, ImageFormat };
 use tokio::{
     sync::Mutex,
     task,
-    fs::{ self, File, write },
-    io::{ self, AsyncBufReadExt, AsyncWriteExt, BufReader },
+    fs::{ self, File },
+    io::{ self, AsyncBufReadExt, BufReader },
     process::Command,
 };
 use regex::Regex;
 use regex::Error as RegexError;
 
+pub mod booru;
+pub mod caption;
+pub mod cli;
+pub mod clip;
+pub mod config;
+pub mod dataset;
+pub mod dtext;
+pub mod e621;
+pub mod encoding;
+pub mod journal;
+pub mod tag_filter;
+pub mod walk;
+pub mod write;
+
+pub use booru::{ Booru, BooruPost, BooruSource, Rating };
+pub use caption::{ Caption, escape_tag, split_tags, unescape_tag, KEEP_SEPARATOR };
+pub use cli::CommonArgs;
+pub use clip::{ ClipTokenizer, CLIP_CONTEXT_LENGTH, CLIP_TOKEN_LIMIT };
+pub use config::{ Config, CONFIG_FILE_NAME };
+pub use dataset::{ Dataset, Sample, Sidecar, SidecarKind };
+pub use e621::TagRelations;
+pub use encoding::{ decode_text, DecodedText };
+pub use tag_filter::{ FilterArgs, FilterMode, TagFilter };
+pub use walk::{
+    walk_builder,
+    FileSelector,
+    Skipped,
+    walk_directory,
+    walk_directory_with,
+    walk_directory_blocking,
+    walk_paths,
+    WalkOptions,
+    WalkSummary,
+    DEFAULT_EXCLUDES,
+    IGNORE_FILE_NAME,
+};
+pub use write::WriteOptions;
+
+/// File extensions recognized as images, in lowercase.
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "jxl", "webp"];
+
 /// Processes a file and adds it to a list if it contains multiple lines.
 ///
 /// # Arguments
@@ -166,7 +222,7 @@ pub fn is_hidden(entry: &DirEntry) -> bool {
     entry
         .file_name()
         .to_str()
-        .map_or(false, |s| s != "." && s != ".." && s.starts_with('.'))
+        .is_some_and(|s| s != "." && s != ".." && s.starts_with('.'))
 }
 
 /// Checks if a directory entry is a git directory.
@@ -206,7 +262,8 @@ pub async fn process_rust_file(
 }
 
 /// Walks through Rust files in a directory and applies a callback function to each file.
-/// Skips hidden folders (except "." and ".."), .git folders, and target folders.
+/// Skips hidden folders (except "." and ".."), .git folders, target folders and anything
+/// excluded by a `.datasetignore` file.
 ///
 /// # Errors
 ///
@@ -214,14 +271,32 @@ pub async fn process_rust_file(
 pub async fn walk_rust_files<F, Fut>(dir: impl AsRef<Path>, callback: F) -> io::Result<()>
     where F: Fn(PathBuf) -> Fut, Fut: std::future::Future<Output = io::Result<()>>
 {
-    let walker = WalkDir::new(dir).follow_links(true);
-
-    for entry in walker
-        .into_iter()
-        .filter_entry(|e| !is_hidden(e) && !is_git_dir(e) && !is_target_dir(e))
-        .filter_map(Result::ok) {
-        let path = entry.path().to_owned();
-        if entry.file_type().is_file() && path.extension().map_or(false, |ext| ext == "rs") {
+    walk_rust_files_with(dir, &WalkOptions::default(), callback).await
+}
+
+/// Walks through Rust files in a directory and applies a callback function to each file,
+/// also skipping paths matching `options.exclude`.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if an exclude glob is invalid or a file cannot be opened or read.
+pub async fn walk_rust_files_with<F, Fut>(
+    dir: impl AsRef<Path>,
+    options: &WalkOptions,
+    callback: F
+)
+    -> io::Result<()>
+    where F: Fn(PathBuf) -> Fut, Fut: std::future::Future<Output = io::Result<()>>
+{
+    let mut walker = walk_builder(dir.as_ref(), options).map_err(io::Error::other)?;
+    walker
+        .follow_links(true)
+        .filter_entry(|e| !(e.file_type().is_some_and(|t| t.is_dir()) && e.file_name() == "target"));
+
+    for entry in walker.build().filter_map(Result::ok) {
+        let is_file = entry.file_type().is_some_and(|t| t.is_file());
+        let path = entry.into_path();
+        if is_file && path.extension().is_some_and(|ext| ext == "rs") {
             callback(path).await?;
         }
     }
@@ -256,61 +331,89 @@ pub async fn read_lines(path: &Path) -> io::Result<Vec<String>> {
 pub async fn process_json_file<F, Fut>(file_path: &Path, processor: F) -> io::Result<()>
     where F: FnOnce(&Value) -> Fut, Fut: std::future::Future<Output = io::Result<()>>
 {
-    let content = fs::read_to_string(file_path).await?;
+    let content = read_file_content(file_path).await?;
     let data: Value = serde_json::from_str(&content)?;
     processor(&data).await
 }
 
 /// Writes content to a file at the specified path.
 ///
+/// Files that already have the given content are left untouched. In dry-run mode only a diff
+/// of the change is printed. Otherwise the original content is recorded in the journal, if one
+/// is active, before the file is written.
+///
 /// # Errors
 ///
 /// Returns an `io::Error` if the file cannot be created or written to.
 #[must_use = "Writes content to a file and requires handling of the result to ensure data is saved"]
 pub async fn write_to_file(path: &Path, content: &str) -> io::Result<()> {
-    let mut file = File::create(path).await?;
-    file.write_all(content.as_bytes()).await
+    if journal::is_dry_run() {
+        match fs::read(path).await {
+            Ok(original) if original == content.as_bytes() => {}
+            Ok(original) => {
+                journal::print_diff(path, "dry run", &decode_text(&original).text, content);
+            }
+            Err(_) => {
+                println!("Would create: {}", path.display());
+                journal::print_diff(path, "dry run", "", content);
+            }
+        }
+        return Ok(());
+    }
+    write_bytes_to_file(path, content.as_bytes()).await
 }
 
-/// Walks through a directory and applies a callback function to each file with the specified extension.
+/// Writes raw bytes to a file at the specified path.
+///
+/// Files that already have the given content are left untouched. In dry-run mode only the
+/// size of the change is printed. Otherwise the original content is recorded in the journal, if
+/// one is active, and the file is replaced atomically according to the current
+/// [`WriteOptions`].
 ///
 /// # Errors
 ///
-/// Returns an `io::Error` if there's an issue with directory traversal or file operations.
-#[must_use = "Walks through a directory and requires handling of the result to ensure proper file processing"]
-pub async fn walk_directory<F, Fut>(
-    dir: impl AsRef<Path>,
-    extension: &str,
-    callback: F
-)
-    -> Result<()>
-    where F: Fn(PathBuf) -> Fut, Fut: std::future::Future<Output = Result<()>> + Send + 'static
-{
-    let dir = dir.as_ref();
-    info!("Starting directory walk in: {dir:?}");
-
-    for entry in WalkDir::new(dir)
-        .into_iter()
-        .filter_entry(|e| {
-            let is_hidden = !is_hidden(e);
-            let is_git_dir = !is_git_dir(e);
-            info!("Entry: {e:?}, is_hidden: {is_hidden}, is_git_dir: {is_git_dir}");
-            is_hidden && is_git_dir
-        })
-        .filter_map(Result::ok) {
-        let path = entry.path().to_owned();
-        info!("Processing path: {path:?}");
-        if path.extension().map_or(false, |ext| ext == extension) {
-            info!("Path matches extension: {path:?}");
-            let path_clone = path.clone();
-            if let Err(e) = task::spawn(callback(path)).await? {
-                warn!("Error processing file: {path_clone:?}. Error: {e}");
-            }
-        }
+/// Returns an `io::Error` if the file cannot be created or written to.
+#[must_use = "Writes bytes to a file and requires handling of the result to ensure data is saved"]
+pub async fn write_bytes_to_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    if fs::read(path).await.is_ok_and(|original| original == content) {
+        return Ok(());
+    }
+    if journal::is_dry_run() {
+        println!("Would write {} bytes to: {}", content.len(), path.display());
+        return Ok(());
     }
+    journal::record_write(path).await?;
+    write::write_atomic(path, content).await
+}
 
-    info!("Finished directory walk in: {dir:?}");
-    Ok(())
+/// Renames a file, recording the rename if a journal is active.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the file cannot be renamed.
+#[must_use = "Renames a file and requires handling of the result to ensure the file is properly renamed"]
+pub async fn rename_file(from: &Path, to: &Path) -> io::Result<()> {
+    if journal::is_dry_run() {
+        println!("Would rename: {} -> {}", from.display(), to.display());
+        return Ok(());
+    }
+    journal::record_rename(from, to).await?;
+    fs::rename(from, to).await
+}
+
+/// Removes a file, recording its content if a journal is active.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the file cannot be removed.
+#[must_use = "Removes a file and requires handling of the result to ensure the file is properly removed"]
+pub async fn remove_file(path: &Path) -> io::Result<()> {
+    if journal::is_dry_run() {
+        println!("Would remove: {}", path.display());
+        return Ok(());
+    }
+    journal::record_remove(path).await?;
+    fs::remove_file(path).await
 }
 
 /// Retrieves JSON metadata from a buffer.
@@ -354,30 +457,16 @@ pub async fn process_safetensors_file(path: &Path) -> Result<()> {
     let json = get_json_metadata(path).await?;
     let pretty_json = serde_json::to_string_pretty(&json)?;
     info!("{pretty_json}");
-    write(path.with_extension("json"), pretty_json).await?;
+    write_to_file(&path.with_extension("json"), &pretty_json).await?;
     Ok(())
 }
 
 /// Determines if the given path is an image file.
 #[must_use = "Determines if the path is an image file and the result should be checked"]
 pub fn is_image_file(path: &Path) -> bool {
-    match path.extension().and_then(|e| e.to_str()) {
-        Some(ext) => matches!(ext.to_lowercase().as_str(), "jpg" | "jpeg" | "png" | "jxl"),
-        None => false,
-    }
-}
-
-/// Checks if a caption file exists and is not empty.
-#[must_use = "Checks if the caption file exists and is not empty and the result should be checked"]
-pub async fn caption_file_exists_and_not_empty(path: &Path) -> bool {
-    if path.exists() {
-        match fs::read_to_string(path).await {
-            Ok(content) => !content.trim().is_empty(),
-            Err(_) => false,
-        }
-    } else {
-        false
-    }
+    path.extension().is_some_and(|ext| {
+        IMAGE_EXTENSIONS.iter().any(|image_ext| ext.eq_ignore_ascii_case(image_ext))
+    })
 }
 
 /// Formats a JSON file to have pretty-printed JSON.
@@ -389,34 +478,39 @@ pub async fn caption_file_exists_and_not_empty(path: &Path) -> bool {
 pub async fn format_json_file(path: PathBuf) -> Result<()> {
     info!("Processing file: {}", path.display());
 
-    let file_content = fs
-        ::read_to_string(path.clone()).await
-        .context("Failed to read file content")?;
+    let file_content = read_file_content(&path).await.context("Failed to read file content")?;
     let json: Value = serde_json::from_str(&file_content).context("Failed to parse JSON")?;
     let pretty_json = serde_json::to_string_pretty(&json).context("Failed to format JSON")?;
-    fs::write(path.clone(), pretty_json).await.context("Failed to write formatted JSON")?;
+    write_to_file(&path, &pretty_json).await.context("Failed to write formatted JSON")?;
 
     info!("Formatted {} successfully.", path.display());
     Ok(())
 }
 
-/// Reads the content of a file.
+/// Reads the content of a text file.
+///
+/// The encoding is detected with [`decode_text`], so UTF-16 and Windows-1252 files are read
+/// as well as UTF-8. A byte order mark is removed and CRLF line endings become LF.
 ///
 /// # Errors
 ///
 /// Returns an `io::Error` if the file cannot be opened or read.
 #[must_use = "Reads the content of a file and requires handling of the result to ensure the content is retrieved"]
-pub async fn read_file_content(file: &str) -> io::Result<String> {
-    fs::read_to_string(file).await
+pub async fn read_file_content(file: impl AsRef<Path>) -> io::Result<String> {
+    let bytes = fs::read(file).await?;
+    Ok(decode_text(&bytes).text)
 }
 
-/// Splits content into tags and sentences.
-#[must_use = "Splits content into tags and sentences and the result should be checked"]
-pub fn split_content(content: &str) -> (Vec<&str>, &str) {
-    let split: Vec<_> = content.split("., ").collect();
-    let tags: Vec<_> = split[0].split(',').collect();
-    let sentences = split.get(1).unwrap_or(&"");
-    (tags, sentences.trim())
+/// Returns the MD5 hash of a file in lowercase hex, the name `rename-to-md5` and e621 give to
+/// images.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the file cannot be read.
+#[must_use = "Hashes a file and requires handling of the result to ensure the hash is retrieved"]
+pub async fn file_md5(path: impl AsRef<Path>) -> io::Result<String> {
+    let bytes = fs::read(path).await?;
+    Ok(format!("{:x}", md5::compute(&bytes)))
 }
 
 /// Renames a file to remove the image extension.
@@ -429,7 +523,7 @@ pub async fn rename_file_without_image_extension(path: &Path) -> io::Result<()>
     if let Some(old_name) = path.to_str() {
         if old_name.contains(".jpeg") || old_name.contains(".png") || old_name.contains(".jpg") {
             let new_name = old_name.replace(".jpeg", "").replace(".png", "").replace(".jpg", "");
-            fs::rename(old_name, &new_name).await?;
+            rename_file(path, Path::new(&new_name)).await?;
             info!("Renamed {old_name} to {new_name}");
         }
     }
@@ -438,23 +532,30 @@ pub async fn rename_file_without_image_extension(path: &Path) -> io::Result<()>
 
 /// Processes a JSON file and converts it to a caption file.
 ///
+/// Tags with a probability above `config.tag_threshold` that `filter` keeps are written, most
+/// likely first, to a caption file with `config.caption_extension`.
+///
 /// # Errors
 ///
 /// Returns an `io::Error` if the file cannot be read, parsed, or written.
 #[must_use = "Processes a JSON file to create a caption file and requires handling of the result to ensure proper conversion"]
-pub async fn process_json_to_caption(input_path: &Path) -> io::Result<()> {
+pub async fn process_json_to_caption(
+    input_path: &Path,
+    config: &Config,
+    filter: &TagFilter
+) -> io::Result<()> {
     if input_path.extension().and_then(|s| s.to_str()) == Some("json") {
-        let content = fs::read_to_string(input_path).await?;
+        let content = read_file_content(input_path).await?;
         let json: Value = serde_json::from_str(&content)?;
 
         if let Value::Object(map) = json {
-            let mut tags: Vec<(String, f64)> = map
+            let mut tags: Vec<(&str, f64)> = map
                 .iter()
                 .filter_map(|(key, value)| {
                     if let Value::Number(num) = value {
                         let probability = num.as_f64().unwrap_or(0.0);
-                        if probability > 0.2 {
-                            Some((key.replace('(', "\\(").replace(')', "\\)"), probability))
+                        if probability > config.tag_threshold && filter.allows(key) {
+                            Some((key.as_str(), probability))
                         } else {
                             None
                         }
@@ -466,22 +567,16 @@ pub async fn process_json_to_caption(input_path: &Path) -> io::Result<()> {
 
             tags.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
 
-            let output_path = input_path.with_extension("txt");
-            let mut output_file = File::create(output_path).await?;
-            output_file.write_all(
-                tags
-                    .iter()
-                    .map(|(tag, _)| tag.clone())
-                    .collect::<Vec<String>>()
-                    .join(", ")
-                    .as_bytes()
-            ).await?;
+            let output_path = input_path.with_extension(&config.caption_extension);
+            let caption = Caption::from_tags(tags.iter().map(|(tag, _)| tag));
+            write_to_file(&output_path, &caption.to_string()).await?;
         }
     }
     Ok(())
 }
 
-/// Deletes files with a specific extension in a directory and its subdirectories.
+/// Deletes files with a specific extension in a directory and its subdirectories,
+/// leaving alone anything excluded by a `.datasetignore` file.
 ///
 /// # Errors
 ///
@@ -490,16 +585,17 @@ pub async fn process_json_to_caption(input_path: &Path) -> io::Result<()> {
 pub async fn delete_files_with_extension(target_dir: &Path, extension: &str) -> io::Result<()> {
     let mut tasks = Vec::new();
 
-    for entry in WalkDir::new(target_dir).into_iter().filter_map(Result::ok) {
-        let path = entry.path().to_owned();
+    let walker = walk_builder(target_dir, &WalkOptions::default()).map_err(io::Error::other)?;
+    for entry in walker.build().filter_map(Result::ok) {
+        let path = entry.into_path();
         if path.is_file() {
             if let Some(file_extension) = path.extension() {
                 if file_extension.eq_ignore_ascii_case(extension) {
                     tasks.push(
                         tokio::spawn(async move {
-                            if let Err(e) = fs::remove_file(&path).await {
+                            if let Err(e) = remove_file(&path).await {
                                 eprintln!("Failed to remove {}: {e}", path.display());
-                            } else {
+                            } else if !journal::is_dry_run() {
                                 println!("Removed: {}", path.display());
                             }
                         })
@@ -526,7 +622,7 @@ pub async fn remove_letterbox(input_path: &Path) -> io::Result<()> {
     // Handle all image formats through image crate
     let img_bytes = fs::read(input_path).await?;
     let img = image::load_from_memory(&img_bytes)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        .map_err(io::Error::other)?;
 
     let (width, height) = img.dimensions();
 
@@ -579,8 +675,8 @@ pub async fn remove_letterbox(input_path: &Path) -> io::Result<()> {
     let mut buf = Vec::new();
     cropped
         .write_to(&mut std::io::Cursor::new(&mut buf), ImageFormat::Png)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    fs::write(input_path, buf).await?;
+        .map_err(io::Error::other)?;
+    write_bytes_to_file(input_path, &buf).await?;
 
     Ok(())
 }
@@ -635,22 +731,33 @@ pub const RUST_ATTRIBUTES: &[&str] = &[
     "debugger_visualizer",
 ];
 
+/// Checks a Rust file, or every Rust file in a directory, for the pedantic clippy warning.
+///
+/// Returns the files that are missing it.
+///
+/// # Errors
+///
+/// Returns an error if the target cannot be canonicalized, is neither a `.rs` file nor a
+/// directory, or a file cannot be read.
+///
+/// # Panics
+///
+/// Panics if the list of files is still shared after the walk, which cannot happen.
 pub async fn check_pedantic(directory: &str) -> Result<Vec<PathBuf>> {
     let files_without_warning = Arc::new(Mutex::new(Vec::new()));
 
     let target = PathBuf::from(directory);
     let canonical_target = target.canonicalize().context("Failed to canonicalize path")?;
 
-    if canonical_target.is_file() && canonical_target.extension().map_or(false, |ext| ext == "rs") {
+    if canonical_target.is_file() && canonical_target.extension().is_some_and(|ext| ext == "rs") {
         let mut guard = files_without_warning.lock().await;
-        process_rust_file(&canonical_target, &mut *guard).await?;
+        process_rust_file(&canonical_target, &mut guard).await?;
     } else if canonical_target.is_dir() {
         walk_rust_files(&canonical_target, |path| {
             let files_without_warning_clone = Arc::clone(&files_without_warning);
-            let path_buf = path.to_path_buf();
             async move {
                 let mut guard = files_without_warning_clone.lock().await;
-                process_rust_file(&path_buf, &mut *guard).await
+                process_rust_file(&path, &mut guard).await
             }
         }).await.context("Failed to walk through Rust files")?;
     } else {
@@ -660,6 +767,17 @@ pub async fn check_pedantic(directory: &str) -> Result<Vec<PathBuf>> {
     Ok(Arc::try_unwrap(files_without_warning).unwrap().into_inner())
 }
 
+/// Checks a `Cargo.toml`, or every `Cargo.toml` in a directory, for the optimization profiles.
+///
+/// Returns the manifests that are missing them.
+///
+/// # Errors
+///
+/// Returns an error if the target is neither a `Cargo.toml` nor a directory, or the walk fails.
+///
+/// # Panics
+///
+/// Panics if a walked path has no file name.
 pub async fn check_optimizations(target: &str) -> Result<Vec<PathBuf>> {
     let target_path = Path::new(target);
     let missing_configs = Arc::new(Mutex::new(Vec::new()));
@@ -688,6 +806,15 @@ pub async fn check_optimizations(target: &str) -> Result<Vec<PathBuf>> {
     Ok(Arc::try_unwrap(missing_configs).unwrap().into_inner())
 }
 
+/// Checks whether a `Cargo.toml` has the dev and release optimization profiles set.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed.
+///
+/// # Panics
+///
+/// Panics if the path is not valid UTF-8.
 pub async fn check_cargo_toml(path: &Path) -> Result<bool> {
     let content = read_file_content(path.to_str().unwrap()).await.context("Failed to read file")?;
     let toml_value: Value = content.parse().context("Failed to parse TOML")?;
@@ -700,7 +827,7 @@ pub async fn check_cargo_toml(path: &Path) -> Result<bool> {
     let Some(dev) = profile.get("dev") else {
         return Ok(false);
     };
-    if dev.get("opt-level").and_then(|v| v.as_i64()) != Some(3) {
+    if dev.get("opt-level").and_then(Value::as_i64) != Some(3) {
         return Ok(false);
     }
 
@@ -709,8 +836,8 @@ pub async fn check_cargo_toml(path: &Path) -> Result<bool> {
         return Ok(false);
     };
     if
-        dev_package.get("opt-level").and_then(|v| v.as_i64()) != Some(3) ||
-        dev_package.get("codegen-units").and_then(|v| v.as_i64()) != Some(1)
+        dev_package.get("opt-level").and_then(Value::as_i64) != Some(3) ||
+        dev_package.get("codegen-units").and_then(Value::as_i64) != Some(1)
     {
         return Ok(false);
     }
@@ -720,10 +847,10 @@ pub async fn check_cargo_toml(path: &Path) -> Result<bool> {
         return Ok(false);
     };
     if
-        release.get("opt-level").and_then(|v| v.as_i64()) != Some(3) ||
-        release.get("lto").and_then(|v| v.as_bool()) != Some(true) ||
-        release.get("codegen-units").and_then(|v| v.as_i64()) != Some(1) ||
-        release.get("strip").and_then(|v| v.as_bool()) != Some(true)
+        release.get("opt-level").and_then(Value::as_i64) != Some(3) ||
+        release.get("lto").and_then(Value::as_bool) != Some(true) ||
+        release.get("codegen-units").and_then(Value::as_i64) != Some(1) ||
+        release.get("strip").and_then(Value::as_bool) != Some(true)
     {
         return Ok(false);
     }
@@ -731,6 +858,17 @@ pub async fn check_cargo_toml(path: &Path) -> Result<bool> {
     Ok(true)
 }
 
+/// Finds every line in the Rust files of a directory that uses one of the given attributes.
+///
+/// Returns the file, line number and line of every match.
+///
+/// # Errors
+///
+/// Returns an error if the attribute regex cannot be built or a file cannot be read.
+///
+/// # Panics
+///
+/// Panics if the list of matches is still shared after the walk, which cannot happen.
 pub async fn check_attributes(
     directory: &str,
     attributes: &[&str]
@@ -754,7 +892,7 @@ pub async fn check_attributes(
                     if re.is_match(line) {
                         matches
                             .lock().await
-                            .push((path.clone(), line_number + 1, line.to_string()));
+                            .push((path.clone(), line_number + 1, line.clone()));
                     }
                 }
                 Ok(())
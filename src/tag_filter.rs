@@ -0,0 +1,248 @@
+// src/tag_filter.rs
+
+// Tag filtering
+//
+// A `TagFilter` decides which tags may stay in a caption. It is built once per run from the
+// `ignored-tags` regexes and the rule file of the config, or the matching command line
+// options, and is shared by the converters and `filter-tags`.
+//
+// A rule file holds one rule per line. Blank lines and lines starting with `#` are skipped.
+//
+// ```text
+// # Literal tags
+// conditional dnp
+// # Globs, any line with a `*`
+// *_(artist)
+// glob:text_*
+// # Regular expressions
+// re:^\d{4}$
+// ```
+//
+// Tags are compared in their e621 form, lowercase with underscores and without escapes, so
+// `long hair`, `long_hair` and `Long Hair` are the same tag. Literals and globs are written
+// in either form, regexes see the e621 form. In blacklist mode, the default, matching tags
+// are removed; in whitelist mode only matching tags are kept. The `ignored-tags` regexes
+// remove tags in both modes.
+
+use std::{ collections::HashSet, path::{ Path, PathBuf } };
+use anyhow::{ Context, Result };
+use clap::ValueEnum;
+use regex::{ Regex, RegexSet };
+use serde::Deserialize;
+
+use crate::{ decode_text, e621::e621_name, Caption, Config };
+
+/// Whether the rules of a rule file name the tags to remove or the tags to keep.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterMode {
+    /// Remove the tags matching a rule
+    #[default]
+    Blacklist,
+    /// Keep only the tags matching a rule
+    Whitelist,
+}
+
+/// Command line options for the tag filter, shared by the tools that filter tags.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct FilterArgs {
+    /// Rule file of tags to filter, overrides `tag-rules` from the config
+    #[arg(long, value_name = "FILE")]
+    pub rules: Option<PathBuf>,
+
+    /// Whether the rules name tags to remove or to keep, overrides `tag-rules-mode` from the
+    /// config
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub rules_mode: Option<FilterMode>,
+}
+
+/// Literal tags and patterns that tags are matched against.
+#[derive(Debug, Clone)]
+struct RuleSet {
+    literals: HashSet<String>,
+    patterns: RegexSet,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self { literals: HashSet::new(), patterns: RegexSet::empty() }
+    }
+}
+
+impl RuleSet {
+    /// Parses the lines of a rule file.
+    fn parse<S: AsRef<str>>(lines: &[S]) -> Result<Self> {
+        let mut literals = HashSet::new();
+        let mut patterns = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let rule = line.as_ref().trim();
+            if rule.is_empty() || rule.starts_with('#') {
+                continue;
+            }
+            if let Some(pattern) = rule.strip_prefix("re:") {
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid regex on line {}: {pattern}", i + 1))?;
+                patterns.push(pattern.to_string());
+            } else if let Some(glob) = rule.strip_prefix("glob:") {
+                patterns.push(glob_to_regex(glob));
+            } else if rule.contains('*') {
+                patterns.push(glob_to_regex(rule));
+            } else {
+                literals.insert(e621_name(rule));
+            }
+        }
+        Ok(Self { literals, patterns: RegexSet::new(patterns)? })
+    }
+
+    /// Returns `true` if a tag in e621 form matches a rule.
+    fn matches(&self, name: &str) -> bool {
+        self.literals.contains(name) || self.patterns.is_match(name)
+    }
+}
+
+/// Converts a glob, where `*` matches anything, to an anchored regex over e621 names.
+fn glob_to_regex(glob: &str) -> String {
+    let parts: Vec<String> = e621_name(glob).split('*').map(regex::escape).collect();
+    format!("^{}$", parts.join(".*"))
+}
+
+/// Decides which tags are kept, from literal tags, globs and regular expressions.
+#[derive(Debug, Clone, Default)]
+pub struct TagFilter {
+    /// Tags removed in any case.
+    blacklist: RuleSet,
+    /// Tags kept in whitelist mode, every other tag is removed.
+    whitelist: Option<RuleSet>,
+}
+
+impl TagFilter {
+    /// Creates a filter from the lines of a rule file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a regex or glob is invalid.
+    pub fn parse<S: AsRef<str>>(lines: &[S], mode: FilterMode) -> Result<Self> {
+        let rules = RuleSet::parse(lines)?;
+        Ok(match mode {
+            FilterMode::Blacklist => Self { blacklist: rules, whitelist: None },
+            FilterMode::Whitelist => {
+                Self { blacklist: RuleSet::default(), whitelist: Some(rules) }
+            }
+        })
+    }
+
+    /// Reads a rule file in any common encoding, such as UTF-16 as saved by Notepad.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or holds an invalid rule.
+    pub fn load(path: &Path, mode: FilterMode) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let content = decode_text(&bytes).text;
+        let lines: Vec<&str> = content.lines().collect();
+        Self::parse(&lines, mode).with_context(|| format!("Invalid rule file {}", path.display()))
+    }
+
+    /// Creates the filter for a dataset: the `ignored-tags` regexes of the config, plus the
+    /// rule file from the command line or the config if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern or the rule file is invalid.
+    pub fn from_config(config: &Config, args: &FilterArgs) -> Result<Self> {
+        let mode = args.rules_mode.unwrap_or(config.tag_rules_mode);
+        let mut filter = match args.rules.as_ref().or(config.tag_rules.as_ref()) {
+            Some(path) => Self::load(path, mode)?,
+            None => Self::default(),
+        };
+
+        // The ignored tags are removed in both modes, so they join the blacklist
+        let patterns = filter.blacklist.patterns.patterns().iter().chain(&config.ignored_tags);
+        filter.blacklist.patterns = RegexSet::new(patterns).context("Invalid tag pattern")?;
+        Ok(filter)
+    }
+
+    /// Returns `true` if the filter keeps a tag, written as an e621 name or as a caption tag.
+    #[must_use = "Determines if the tag is kept and the result should be checked"]
+    pub fn allows(&self, tag: &str) -> bool {
+        let name = e621_name(tag);
+        !self.blacklist.matches(&name) &&
+            self.whitelist.as_ref().is_none_or(|whitelist| whitelist.matches(&name))
+    }
+
+    /// Removes the tags of a caption the filter does not keep. Keep tokens and sentences are
+    /// left alone.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a tag was removed.
+    pub fn apply(&self, caption: &mut Caption) -> bool {
+        let len = caption.tags.len();
+        caption.tags.retain(|tag| self.allows(tag));
+        caption.tags.len() != len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &[&str] = &[
+        "# Comment",
+        "",
+        "Long Hair",
+        "*_(artist)",
+        "glob:text*",
+        r"re:^\d{4}$",
+    ];
+
+    #[test]
+    fn test_blacklist() {
+        let filter = TagFilter::parse(RULES, FilterMode::Blacklist).unwrap();
+        for tag in ["long hair", "long_hair", "foo (artist)", r"foo \(artist\)", "text", "2024"] {
+            assert!(!filter.allows(tag), "{tag}");
+        }
+        for tag in ["short hair", "artist", "context", "20245"] {
+            assert!(filter.allows(tag), "{tag}");
+        }
+    }
+
+    #[test]
+    fn test_whitelist() {
+        let filter = TagFilter::parse(RULES, FilterMode::Whitelist).unwrap();
+        let mut caption = Caption::parse("feral ||| long hair, solo, text bubble, A wolf howls.");
+        assert!(filter.apply(&mut caption));
+        assert_eq!(caption.to_string(), "feral ||| long hair, text bubble, A wolf howls.");
+    }
+
+    #[test]
+    fn test_from_config() {
+        let filter = TagFilter::from_config(&Config::default(), &FilterArgs::default()).unwrap();
+        assert!(!filter.allows("conditional_dnp"));
+        assert!(!filter.allows("conditional dnp"));
+        assert!(!filter.allows("16:9"));
+        assert!(filter.allows("solo"));
+
+        assert!(TagFilter::parse(&["re:("], FilterMode::Blacklist).is_err());
+    }
+
+    #[test]
+    fn test_load_encodings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let utf8_bom = temp_dir.path().join("bom.txt");
+        std::fs::write(&utf8_bom, "\u{feff}long hair\r\nsolo\r\n").unwrap();
+        let utf16 = temp_dir.path().join("utf16.txt");
+        let bytes: Vec<u8> = "\u{feff}long hair\r\nsolo\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        std::fs::write(&utf16, bytes).unwrap();
+
+        for path in [utf8_bom, utf16] {
+            let filter = TagFilter::load(&path, FilterMode::Blacklist).unwrap();
+            assert!(!filter.allows("long hair"), "{}", path.display());
+            assert!(!filter.allows("solo"), "{}", path.display());
+        }
+    }
+}
@@ -0,0 +1,452 @@
+// src/walk.rs
+
+// Directory walking
+//
+// `walk_directory` visits every file selected by a `FileSelector` below a directory and runs a
+// callback on it. A selector is a set of case-insensitive extensions, with a preset for all
+// images, or an arbitrary predicate on the path. Callbacks run concurrently, bounded by
+// `WalkOptions::jobs`, and CPU-bound work can be moved to blocking threads with
+// `walk_directory_blocking`. Instead of stopping at the first error, per-file errors are
+// collected into a `WalkSummary` that binaries report at the end of a run. A callback that
+// returns `Skipped` leaves the file alone without failing, for files that match the selector
+// but turn out to be something else. `walk_paths` runs callbacks the same way over paths found
+// otherwise, such as the images of a `Dataset`.
+//
+// Every walk skips hidden files and folders, honours gitignore-style `.datasetignore` files
+// (nested, with `!` negation) in the walked directory and its parents, and skips paths
+// matching the `--exclude` globs in `WalkOptions::exclude`.
+
+use std::{ collections::HashMap, fmt, future::Future, path::{ Path, PathBuf }, sync::Arc };
+use log::{ info, warn };
+use ignore::{ overrides::OverrideBuilder, WalkBuilder };
+use anyhow::{ anyhow, Context, Result };
+use tokio::task::{ JoinError, JoinSet };
+
+use crate::IMAGE_EXTENSIONS;
+
+/// The name of the gitignore-style files that exclude paths from every walk.
+pub const IGNORE_FILE_NAME: &str = ".datasetignore";
+
+/// Globs excluded by default, so sample prompts and word lists are never treated as captions.
+pub const DEFAULT_EXCLUDES: &[&str] =
+    &["sample-prompts.txt", "*-sample-prompts.txt", "wordfreq.txt"];
+
+/// Decides which files a directory walk visits.
+///
+/// Plain extensions convert into a selector, so `walk_directory(dir, "txt", ...)` visits
+/// every `.txt` and `.TXT` file.
+#[derive(Clone)]
+pub enum FileSelector {
+    /// Files with one of these extensions, compared case-insensitively.
+    Extensions(Vec<String>),
+    /// Files for which the predicate returns `true`.
+    Predicate(Arc<dyn Fn(&Path) -> bool + Send + Sync>),
+}
+
+impl FileSelector {
+    /// Selects files with any of the given extensions.
+    pub fn extensions<I, S>(extensions: I) -> Self
+        where I: IntoIterator<Item = S>, S: Into<String>
+    {
+        Self::Extensions(extensions.into_iter().map(Into::into).collect())
+    }
+
+    /// Selects every image file, using the same extensions as [`crate::is_image_file`].
+    #[must_use = "Creates a file selector that should be passed to a directory walk"]
+    pub fn images() -> Self {
+        Self::extensions(IMAGE_EXTENSIONS.iter().copied())
+    }
+
+    /// Selects files for which `predicate` returns `true`.
+    pub fn predicate(predicate: impl Fn(&Path) -> bool + Send + Sync + 'static) -> Self {
+        Self::Predicate(Arc::new(predicate))
+    }
+
+    /// Returns `true` if the walk should visit `path`.
+    #[must_use = "Determines if the file is selected and the result should be checked"]
+    pub fn matches(&self, path: &Path) -> bool {
+        match self {
+            Self::Extensions(extensions) =>
+                path.extension().is_some_and(|ext| {
+                    extensions.iter().any(|wanted| ext.eq_ignore_ascii_case(wanted))
+                }),
+            Self::Predicate(predicate) => predicate(path),
+        }
+    }
+}
+
+impl std::fmt::Debug for FileSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Extensions(extensions) => {
+                f.debug_tuple("Extensions").field(extensions).finish()
+            }
+            Self::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+impl From<&str> for FileSelector {
+    fn from(extension: &str) -> Self {
+        Self::extensions([extension])
+    }
+}
+
+impl From<&[&str]> for FileSelector {
+    fn from(extensions: &[&str]) -> Self {
+        Self::extensions(extensions.iter().copied())
+    }
+}
+
+impl<const N: usize> From<[&str; N]> for FileSelector {
+    fn from(extensions: [&str; N]) -> Self {
+        Self::extensions(extensions)
+    }
+}
+
+/// Options shared by all directory walks.
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// The maximum number of files processed at the same time.
+    pub jobs: usize,
+    /// Gitignore-style globs of paths to skip, on top of any `.datasetignore` files.
+    pub exclude: Vec<String>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            jobs: std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get),
+            exclude: DEFAULT_EXCLUDES.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Creates a walker over `dir` that skips hidden entries, paths listed in `.datasetignore`
+/// files and paths matching `options.exclude`.
+///
+/// # Errors
+///
+/// Returns an error if one of the exclude globs is invalid.
+pub fn walk_builder(dir: &Path, options: &WalkOptions) -> Result<WalkBuilder> {
+    let mut overrides = OverrideBuilder::new(dir);
+    for glob in &options.exclude {
+        overrides
+            .add(&format!("!{glob}"))
+            .with_context(|| format!("Invalid exclude pattern: {glob}"))?;
+    }
+    let overrides = overrides.build().context("Failed to build exclude patterns")?;
+
+    let mut builder = WalkBuilder::new(dir);
+    builder
+        .standard_filters(false)
+        .hidden(true)
+        .parents(true)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME);
+    builder.overrides(overrides);
+    Ok(builder)
+}
+
+/// Returned by a walk callback for a file it has nothing to do with, such as a JSON file that
+/// is not post metadata. The file is counted as skipped rather than failed.
+#[derive(Debug)]
+pub struct Skipped(pub String);
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+/// The outcome of a directory walk.
+#[derive(Debug, Default)]
+pub struct WalkSummary {
+    /// Files the callback ran on successfully.
+    pub processed: usize,
+    /// Files that did not match the walk, or that the callback returned `Skipped` for, and
+    /// were left alone.
+    pub skipped: usize,
+    /// Files the callback failed on, with the error it returned.
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+impl WalkSummary {
+    /// Returns `true` if no file failed.
+    #[must_use = "Determines if the walk succeeded and the result should be checked"]
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Adds the counts and failures of another walk to this one.
+    pub fn merge(&mut self, other: WalkSummary) {
+        self.processed += other.processed;
+        self.skipped += other.skipped;
+        self.failed.extend(other.failed);
+    }
+
+    /// Prints the summary, listing every failed file on stderr.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any file failed, so binaries can exit with a non-zero status.
+    pub fn report(&self) -> Result<()> {
+        for (path, e) in &self.failed {
+            eprintln!("Failed: {}: {e:#}", path.display());
+        }
+        println!(
+            "Processed {} files, skipped {}, failed {}.",
+            self.processed,
+            self.skipped,
+            self.failed.len()
+        );
+        if self.is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("{} files failed", self.failed.len()))
+        }
+    }
+
+    fn record(&mut self, path: PathBuf, result: Result<()>) {
+        match result {
+            Ok(()) => {
+                self.processed += 1;
+            }
+            Err(e) if e.is::<Skipped>() => {
+                info!("Skipping file: {path:?}. {e}");
+                self.skipped += 1;
+            }
+            Err(e) => {
+                warn!("Error processing file: {path:?}. Error: {e}");
+                self.failed.push((path, e));
+            }
+        }
+    }
+
+    fn record_join(
+        &mut self,
+        paths: &mut HashMap<tokio::task::Id, PathBuf>,
+        joined: Result<(tokio::task::Id, Result<()>), JoinError>
+    ) {
+        match joined {
+            Ok((id, result)) => {
+                let path = paths.remove(&id).unwrap_or_default();
+                self.record(path, result);
+            }
+            Err(e) => {
+                let path = paths.remove(&e.id()).unwrap_or_default();
+                self.record(path, Err(anyhow!("Task failed: {e}")));
+            }
+        }
+    }
+}
+
+/// Collects the files below `dir` picked by the selector, counting the others as skipped.
+fn matching_files(
+    dir: &Path,
+    selector: &FileSelector,
+    options: &WalkOptions,
+    summary: &mut WalkSummary
+) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in walk_builder(dir, options)?.build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Skipping unreadable entry: {e}");
+                continue;
+            }
+        };
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let path = entry.into_path();
+        if selector.matches(&path) {
+            files.push(path);
+        } else {
+            summary.skipped += 1;
+        }
+    }
+    Ok(files)
+}
+
+/// Walks through a directory and applies a callback function to each file picked by the
+/// selector, using the default [`WalkOptions`].
+///
+/// # Errors
+///
+/// Returns an error if an exclude glob is invalid. Errors returned by the callback are
+/// collected in the [`WalkSummary`] instead.
+#[must_use = "Walks through a directory and the summary should be reported"]
+pub async fn walk_directory<F, Fut>(
+    dir: impl AsRef<Path>,
+    files: impl Into<FileSelector>,
+    callback: F
+)
+    -> Result<WalkSummary>
+    where F: Fn(PathBuf) -> Fut, Fut: Future<Output = Result<()>> + Send + 'static
+{
+    walk_directory_with(dir, files, &WalkOptions::default(), callback).await
+}
+
+/// Walks through a directory and applies a callback function to each file picked by the
+/// selector, running up to `options.jobs` callbacks at the same time.
+///
+/// # Errors
+///
+/// Returns an error if an exclude glob is invalid. Errors returned by the callback are
+/// collected in the [`WalkSummary`] instead.
+#[must_use = "Walks through a directory and the summary should be reported"]
+pub async fn walk_directory_with<F, Fut>(
+    dir: impl AsRef<Path>,
+    files: impl Into<FileSelector>,
+    options: &WalkOptions,
+    callback: F
+)
+    -> Result<WalkSummary>
+    where F: Fn(PathBuf) -> Fut, Fut: Future<Output = Result<()>> + Send + 'static
+{
+    let dir = dir.as_ref();
+    info!("Starting directory walk in: {dir:?}");
+
+    let mut summary = WalkSummary::default();
+    let files = matching_files(dir, &files.into(), options, &mut summary)?;
+    summary.merge(walk_paths(files, options, callback).await);
+
+    info!("Finished directory walk in: {dir:?}");
+    Ok(summary)
+}
+
+/// Applies a callback function to each of the given paths, such as the samples of a
+/// [`Dataset`](crate::Dataset), running up to `options.jobs` callbacks at the same time.
+#[must_use = "Processes the paths and the summary should be reported"]
+pub async fn walk_paths<F, Fut>(
+    paths: impl IntoIterator<Item = PathBuf>,
+    options: &WalkOptions,
+    callback: F
+)
+    -> WalkSummary
+    where F: Fn(PathBuf) -> Fut, Fut: Future<Output = Result<()>> + Send + 'static
+{
+    let mut summary = WalkSummary::default();
+    let mut tasks = JoinSet::new();
+    let mut running = HashMap::new();
+
+    for path in paths {
+        while tasks.len() >= options.jobs.max(1) {
+            if let Some(joined) = tasks.join_next_with_id().await {
+                summary.record_join(&mut running, joined);
+            }
+        }
+        info!("Processing path: {path:?}");
+        let handle = tasks.spawn(callback(path.clone()));
+        running.insert(handle.id(), path);
+    }
+    while let Some(joined) = tasks.join_next_with_id().await {
+        summary.record_join(&mut running, joined);
+    }
+    summary
+}
+
+/// Walks through a directory and applies a CPU-bound callback function to each file picked by
+/// the selector on tokio's blocking threads, running up to `options.jobs` at a time.
+///
+/// # Errors
+///
+/// Returns an error if an exclude glob is invalid. Errors returned by the callback are
+/// collected in the [`WalkSummary`] instead.
+#[must_use = "Walks through a directory and the summary should be reported"]
+pub async fn walk_directory_blocking<F>(
+    dir: impl AsRef<Path>,
+    files: impl Into<FileSelector>,
+    options: &WalkOptions,
+    callback: F
+)
+    -> Result<WalkSummary>
+    where F: Fn(PathBuf) -> Result<()> + Send + Sync + 'static
+{
+    let dir = dir.as_ref();
+    info!("Starting blocking directory walk in: {dir:?}");
+
+    let callback = Arc::new(callback);
+    let mut summary = WalkSummary::default();
+    let mut tasks = JoinSet::new();
+    let mut paths = HashMap::new();
+
+    for path in matching_files(dir, &files.into(), options, &mut summary)? {
+        while tasks.len() >= options.jobs.max(1) {
+            if let Some(joined) = tasks.join_next_with_id().await {
+                summary.record_join(&mut paths, joined);
+            }
+        }
+        info!("Processing path: {path:?}");
+        let callback = Arc::clone(&callback);
+        let task_path = path.clone();
+        let handle = tasks.spawn_blocking(move || callback(task_path));
+        paths.insert(handle.id(), path);
+    }
+    while let Some(joined) = tasks.join_next_with_id().await {
+        summary.record_join(&mut paths, joined);
+    }
+
+    info!("Finished blocking directory walk in: {dir:?}");
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_datasetignore_and_excludes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::create_dir_all(root.join("nested")).unwrap();
+        for file in [
+            "a.txt",
+            "b-sample-prompts.txt",
+            "notes/todo.txt",
+            "nested/c.txt",
+            "nested/d.txt",
+            "nested/keep.txt",
+            ".hidden.txt",
+        ] {
+            fs::write(root.join(file), "tag").unwrap();
+        }
+        fs::write(root.join(IGNORE_FILE_NAME), "notes/\n").unwrap();
+        fs::write(root.join("nested").join(IGNORE_FILE_NAME), "*.txt\n!keep.txt\n").unwrap();
+
+        let mut options = WalkOptions::default();
+        options.exclude.push("a.txt".to_string());
+
+        let mut summary = WalkSummary::default();
+        let mut files: Vec<_> = matching_files(root, &"txt".into(), &options, &mut summary)
+            .unwrap()
+            .into_iter()
+            .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec![PathBuf::from("nested/keep.txt")]);
+    }
+
+    #[test]
+    fn test_file_selector() {
+        let images = FileSelector::images();
+        assert!(images.matches(Path::new("a/b.PNG")));
+        assert!(images.matches(Path::new("a/b.jxl")));
+        assert!(!images.matches(Path::new("a/b.txt")));
+
+        let captions = FileSelector::from(["txt", "caption"]);
+        assert!(captions.matches(Path::new("b.TXT")));
+        assert!(captions.matches(Path::new("b.caption")));
+        assert!(!captions.matches(Path::new("b")));
+
+        let cargo = FileSelector::predicate(|path| path.ends_with("Cargo.toml"));
+        assert!(cargo.matches(Path::new("crate/Cargo.toml")));
+        assert!(!cargo.matches(Path::new("crate/rustfmt.toml")));
+    }
+}
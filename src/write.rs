@@ -0,0 +1,154 @@
+// src/write.rs
+
+// Atomic writes
+//
+// Library helpers never write a file in place. The new content goes to a hidden temporary
+// file next to the target, which is flushed to disk and then renamed over the target, so a
+// crash or Ctrl-C mid-run leaves either the old or the new file but never half of one. The
+// replacement keeps the permissions of the original and, with `--preserve-mtime`, its
+// modification time. With `--backup`, the original is first copied to `<name>.bak`.
+
+use std::{
+    fs::{ FileTimes, Metadata },
+    path::{ Path, PathBuf },
+    sync::atomic::{ AtomicBool, AtomicUsize, Ordering },
+};
+use tokio::{ fs::{ self, OpenOptions }, io::{ self, AsyncWriteExt }, task };
+
+use crate::journal;
+
+static BACKUP: AtomicBool = AtomicBool::new(false);
+static PRESERVE_MTIME: AtomicBool = AtomicBool::new(false);
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+/// Options for how library helpers replace existing files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Copy the original file to `<name>.bak` before replacing it for the first time.
+    pub backup: bool,
+    /// Keep the modification time of the original file.
+    pub preserve_mtime: bool,
+}
+
+impl WriteOptions {
+    /// Returns the write options of the current process.
+    #[must_use = "Returns the configured write options and the result should be used"]
+    pub fn current() -> Self {
+        Self {
+            backup: BACKUP.load(Ordering::Relaxed),
+            preserve_mtime: PRESERVE_MTIME.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Makes these the write options of the current process.
+    pub fn install(self) {
+        BACKUP.store(self.backup, Ordering::Relaxed);
+        PRESERVE_MTIME.store(self.preserve_mtime, Ordering::Relaxed);
+    }
+}
+
+/// Returns the path a file is backed up to, `foo.txt` becoming `foo.txt.bak`.
+#[must_use = "Returns the backup path and the result should be used"]
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Returns a hidden, unused path next to `path` for the new content.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(
+        format!(".{}.{}.tmp", std::process::id(), NEXT_TEMP.fetch_add(1, Ordering::Relaxed))
+    );
+    path.with_file_name(name)
+}
+
+/// Atomically replaces the content of `path`, creating it if it does not exist.
+///
+/// An existing `.bak` file is never overwritten, so it always holds the oldest original.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the backup, the temporary file or the rename fails. The
+/// original file is left untouched in that case.
+pub(crate) async fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let options = WriteOptions::current();
+    let original = match fs::metadata(path).await {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    if options.backup && original.is_some() {
+        let backup = backup_path(path);
+        if !fs::try_exists(&backup).await? {
+            journal::record_write(&backup).await?;
+            fs::copy(path, &backup).await?;
+        }
+    }
+
+    let temp = temp_path(path);
+    let mut result = write_temp(&temp, content, original.as_ref(), options.preserve_mtime).await;
+    if result.is_ok() {
+        result = fs::rename(&temp, path).await;
+    }
+    if result.is_err() {
+        // Best effort, the error that got us here is the one worth reporting
+        let _ = fs::remove_file(&temp).await;
+    }
+    result
+}
+
+async fn write_temp(
+    temp: &Path,
+    content: &[u8],
+    original: Option<&Metadata>,
+    preserve_mtime: bool
+) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(temp).await?;
+    file.write_all(content).await?;
+
+    let mut times = None;
+    if let Some(metadata) = original {
+        file.set_permissions(metadata.permissions()).await?;
+        if preserve_mtime {
+            times = Some(FileTimes::new().set_modified(metadata.modified()?));
+        }
+    }
+
+    let file = file.into_std().await;
+    task::spawn_blocking(move || {
+        if let Some(times) = times {
+            file.set_times(times)?;
+        }
+        file.sync_all()
+    }).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_write_atomic_replaces_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("caption.txt");
+
+        write_atomic(&path, b"first").await.unwrap();
+        write_atomic(&path, b"second").await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        // Only the caption is left, no temporary files
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_backup_path() {
+        assert_eq!(backup_path(Path::new("dir/foo.txt")), PathBuf::from("dir/foo.txt.bak"));
+    }
+}
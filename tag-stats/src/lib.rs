@@ -0,0 +1,390 @@
+// tag-stats\src\lib.rs
+
+// This program scans the caption files in a directory and its subdirectories and reports how
+// often each tag is used, how the tags are spread over the folders of the dataset and which
+// pairs of tags show up together most often, to find over-represented tags before training.
+//
+// A tag counts once per caption, keep tokens included, and every count comes with its share
+// of the captions. The report is printed as text, or as CSV or JSON with `--format csv|json`.
+// `--top <n>` limits every list to its n most frequent entries, 0 lists everything. With
+// `--output <file>` the report is written to a file instead, for example `wordfreq.txt`,
+// which directory walks always skip.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use anyhow::{ bail, Result };
+use clap::ValueEnum;
+use dataset_tools::{
+    read_file_content,
+    unescape_tag,
+    walk_directory_with,
+    write_to_file,
+    Caption,
+    CommonArgs,
+};
+use serde::Serialize;
+use std::{
+    collections::{ BTreeMap, HashMap },
+    io::{ self, stdout, Write },
+    path::{ Path, PathBuf },
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+/// Report tag frequencies, per-folder breakdowns and co-occurring tag pairs
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Directory to search for caption files
+    pub directory: Option<PathBuf>,
+
+    /// Output format of the report
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Only list the N most frequent tags and pairs, 0 lists everything
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 50)]
+    pub top: usize,
+
+    /// Write the report to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+/// The output format of the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned tables for humans
+    Text,
+    /// One row per tag, folder tag and pair
+    Csv,
+    /// A single JSON object
+    Json,
+}
+
+/// Tag counts collected from captions.
+#[derive(Debug, Default)]
+pub struct TagStats {
+    /// Number of captions seen.
+    pub captions: u32,
+    /// Number of captions using each tag.
+    pub tags: HashMap<String, u32>,
+    /// Counts per folder, relative to the scanned directory.
+    pub folders: BTreeMap<String, FolderStats>,
+    /// Number of captions using both tags of a pair, the first tag sorting before the second.
+    pub pairs: HashMap<(String, String), u32>,
+}
+
+/// Tag counts of the captions in one folder.
+#[derive(Debug, Default)]
+pub struct FolderStats {
+    /// Number of captions in the folder.
+    pub captions: u32,
+    /// Number of captions in the folder using each tag.
+    pub tags: HashMap<String, u32>,
+}
+
+impl TagStats {
+    /// Counts the tags of a caption found in `folder`.
+    pub fn add(&mut self, folder: &str, caption: &Caption) {
+        let mut tags: Vec<String> = caption.keep_tokens
+            .iter()
+            .map(|token| unescape_tag(token))
+            .chain(caption.tag_names())
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+
+        self.captions += 1;
+        let folder = self.folders.entry(folder.to_string()).or_default();
+        folder.captions += 1;
+        for (i, tag) in tags.iter().enumerate() {
+            *self.tags.entry(tag.clone()).or_default() += 1;
+            *folder.tags.entry(tag.clone()).or_default() += 1;
+            for other in &tags[i + 1..] {
+                *self.pairs.entry((tag.clone(), other.clone())).or_default() += 1;
+            }
+        }
+    }
+
+    /// Returns the report of the most frequent entries, `top` per list or all of them if it
+    /// is 0.
+    #[must_use = "Builds the report and the result should be used"]
+    pub fn report(&self, top: usize) -> Report {
+        let tag_counts = |counts: &HashMap<String, u32>, captions| {
+            most_frequent(counts, top)
+                .into_iter()
+                .map(|(tag, count)| TagCount { tag, count, share: share(count, captions) })
+                .collect()
+        };
+
+        Report {
+            captions: self.captions,
+            unique_tags: self.tags.len(),
+            tags: tag_counts(&self.tags, self.captions),
+            folders: self.folders
+                .iter()
+                .map(|(folder, stats)| FolderReport {
+                    folder: folder.clone(),
+                    captions: stats.captions,
+                    tags: tag_counts(&stats.tags, stats.captions),
+                })
+                .collect(),
+            pairs: most_frequent(&self.pairs, top)
+                .into_iter()
+                .map(|((first, second), count)| PairCount {
+                    tags: [first, second],
+                    count,
+                    share: share(count, self.captions),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The most frequent tags and pairs, overall and per folder.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// Number of captions scanned.
+    pub captions: u32,
+    /// Number of distinct tags, including those left out of the lists.
+    pub unique_tags: usize,
+    /// The most frequent tags.
+    pub tags: Vec<TagCount>,
+    /// The most frequent tags of every folder.
+    pub folders: Vec<FolderReport>,
+    /// The pairs of tags most often used together.
+    pub pairs: Vec<PairCount>,
+}
+
+/// A tag and the number of captions using it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u32,
+    /// The fraction of the captions using the tag, from 0 to 1.
+    pub share: f64,
+}
+
+/// The most frequent tags of a folder.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FolderReport {
+    /// The folder relative to the scanned directory, `.` for the directory itself.
+    pub folder: String,
+    pub captions: u32,
+    pub tags: Vec<TagCount>,
+}
+
+/// Two tags and the number of captions using both.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PairCount {
+    pub tags: [String; 2],
+    pub count: u32,
+    /// The fraction of all captions using both tags, from 0 to 1.
+    pub share: f64,
+}
+
+fn share(count: u32, captions: u32) -> f64 {
+    if captions == 0 { 0.0 } else { f64::from(count) / f64::from(captions) }
+}
+
+/// Returns the `top` entries with the highest counts, ties sorted by key.
+fn most_frequent<K: Ord + Clone>(counts: &HashMap<K, u32>, top: usize) -> Vec<(K, u32)> {
+    let mut sorted: Vec<_> = counts
+        .iter()
+        .map(|(key, &count)| (key.clone(), count))
+        .collect();
+    sorted.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    if top > 0 {
+        sorted.truncate(top);
+    }
+    sorted
+}
+
+/// Returns the folder of a caption relative to the scanned directory, with `/` separators.
+fn folder_of(root: &Path, path: &Path) -> String {
+    let parent = path.parent().unwrap_or(root);
+    let relative = parent.strip_prefix(root).unwrap_or(parent);
+    if relative.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        relative.to_string_lossy().replace('\\', "/")
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes the report in the given format.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the output cannot be written.
+pub fn write_report(format: OutputFormat, report: &Report, mut out: impl Write) -> io::Result<()> {
+    match format {
+        OutputFormat::Text => {
+            writeln!(out, "{} captions, {} unique tags", report.captions, report.unique_tags)?;
+            writeln!(out, "\nMost frequent tags:")?;
+            for tag in &report.tags {
+                writeln!(out, "{:>8} {:>6.1}%  {}", tag.count, tag.share * 100.0, tag.tag)?;
+            }
+            for folder in &report.folders {
+                writeln!(out, "\nFolder {} ({} captions):", folder.folder, folder.captions)?;
+                for tag in &folder.tags {
+                    writeln!(out, "{:>8} {:>6.1}%  {}", tag.count, tag.share * 100.0, tag.tag)?;
+                }
+            }
+            writeln!(out, "\nMost frequent pairs:")?;
+            for pair in &report.pairs {
+                writeln!(
+                    out,
+                    "{:>8} {:>6.1}%  {} + {}",
+                    pair.count,
+                    pair.share * 100.0,
+                    pair.tags[0],
+                    pair.tags[1]
+                )?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "kind,folder,tag,other_tag,count,share")?;
+            for tag in &report.tags {
+                writeln!(out, "tag,,{},,{},{:.4}", csv_field(&tag.tag), tag.count, tag.share)?;
+            }
+            for folder in &report.folders {
+                for tag in &folder.tags {
+                    writeln!(
+                        out,
+                        "folder,{},{},,{},{:.4}",
+                        csv_field(&folder.folder),
+                        csv_field(&tag.tag),
+                        tag.count,
+                        tag.share
+                    )?;
+                }
+            }
+            for pair in &report.pairs {
+                writeln!(
+                    out,
+                    "pair,,{},{},{},{:.4}",
+                    csv_field(&pair.tags[0]),
+                    csv_field(&pair.tags[1]),
+                    pair.count,
+                    pair.share
+                )?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, report)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
+
+/// Runs the tool.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked, any caption cannot be read or the
+/// report cannot be written.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init()?;
+    let directory = common.dir_or(args.directory);
+    let config = common.config(&directory)?;
+
+    let stats = Arc::new(Mutex::new(TagStats::default()));
+    let root = Arc::new(directory.clone());
+    let captions = config.caption_extension.as_str();
+    let summary = walk_directory_with(&directory, captions, &common.walk_options(), |path| {
+        let stats = Arc::clone(&stats);
+        let root = Arc::clone(&root);
+        async move {
+            let caption = Caption::parse(&read_file_content(&path).await?);
+            stats.lock().await.add(&folder_of(&root, &path), &caption);
+            Ok(())
+        }
+    }).await?;
+
+    // Keep stdout clean for CSV and JSON, only failures are listed, on stderr
+    if args.output.is_some() || args.format == OutputFormat::Text {
+        summary.report()?;
+    } else {
+        for (path, e) in &summary.failed {
+            eprintln!("Failed: {}: {e:#}", path.display());
+        }
+        if !summary.is_success() {
+            bail!("{} files failed", summary.failed.len());
+        }
+    }
+
+    let report = stats.lock().await.report(args.top);
+    if let Some(output) = args.output {
+        let mut content = Vec::new();
+        write_report(args.format, &report, &mut content)?;
+        write_to_file(&output, &String::from_utf8(content)?).await?;
+    } else {
+        if args.format == OutputFormat::Text {
+            println!();
+        }
+        write_report(args.format, &report, stdout().lock())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> TagStats {
+        let mut stats = TagStats::default();
+        stats.add(".", &Caption::parse("feral ||| solo, wolf, wolf, A wolf in the snow."));
+        stats.add("cats", &Caption::parse("solo, cat"));
+        stats.add("cats", &Caption::parse(r"cat, solo, hi\, there"));
+        stats
+    }
+
+    #[test]
+    fn test_report() {
+        let report = stats().report(2);
+        assert_eq!(report.captions, 3);
+        assert_eq!(report.unique_tags, 5);
+
+        let tags: Vec<_> = report.tags.iter().map(|tag| (tag.tag.as_str(), tag.count)).collect();
+        assert_eq!(tags, [("solo", 3), ("cat", 2)]);
+        assert!((report.tags[1].share - 2.0 / 3.0).abs() < f64::EPSILON);
+
+        assert_eq!(report.folders.len(), 2);
+        assert_eq!(report.folders[1].folder, "cats");
+        assert_eq!(report.folders[1].captions, 2);
+        assert_eq!(report.pairs[0].tags, ["cat", "solo"]);
+        assert_eq!(report.pairs[0].count, 2);
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let report = stats().report(0);
+        let mut out = Vec::new();
+        write_report(OutputFormat::Csv, &report, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("kind,folder,tag,other_tag,count,share"));
+        assert_eq!(lines.next(), Some("tag,,solo,,3,1.0000"));
+        assert!(csv.contains("folder,cats,\"hi, there\",,1,0.5000\n"));
+        assert!(csv.contains("pair,,cat,solo,2,0.6667\n"));
+    }
+
+    #[test]
+    fn test_folder_of() {
+        let root = Path::new("dataset");
+        assert_eq!(folder_of(root, &root.join("a.txt")), ".");
+        assert_eq!(folder_of(root, &root.join("cats").join("black").join("a.txt")), "cats/black");
+    }
+}
@@ -0,0 +1,26 @@
+// tag-stats\src\main.rs
+
+// Standalone `tag-stats` binary, the same tool is available as `dataset-tools tag-stats`.
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use clap::Parser;
+use dataset_tools::CommonArgs;
+
+#[derive(Parser)]
+#[command(name = "tag-stats", version)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    // Last, so the tool's description becomes the about text
+    #[command(flatten)]
+    args: tag_stats::Args,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    tag_stats::run(cli.args, &cli.common).await
+}
@@ -0,0 +1,78 @@
+// undo\src\lib.rs
+
+// This program reverts the changes recorded in a journal written by another tool's
+// `--journal <dir>` option. Pass `--dry-run` to only list what would be reverted.
+//
+// Usage: ./undo.exe [--dry-run] <journal_directory>
+
+// Turn clippy into a real nerd
+#![warn(clippy::all, clippy::pedantic)]
+
+use dataset_tools::{ journal, CommonArgs };
+use std::path::PathBuf;
+use anyhow::{ bail, Result };
+
+/// Revert the changes recorded in a journal
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Journal directory written by a previous `--journal <dir>` run
+    pub journal_dir: PathBuf,
+}
+
+/// Runs the tool.
+///
+/// The journal is read, not written, so `--journal` cannot be combined with it.
+///
+/// # Errors
+///
+/// Returns an error if `--journal` is given or the journal cannot be reverted.
+pub async fn run(args: Args, common: &CommonArgs) -> Result<()> {
+    common.init_logging();
+    if common.journal.is_some() {
+        bail!("Pass the journal to undo as its directory, not with --journal");
+    }
+    journal::init(common.dry_run, None)?;
+    journal::undo(&args.journal_dir).await?;
+
+    println!("Undo complete.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dataset_tools::{ remove_file, rename_file, write_to_file };
+    use tempfile::TempDir;
+
+    // The journal is configured once per process, so this is the only test that records one
+    #[tokio::test]
+    async fn test_undo_round_trip() {
+        let data = TempDir::new().unwrap();
+        let journal_dir = TempDir::new().unwrap();
+        let path = |name: &str| data.path().join(name);
+        std::fs::write(path("written.txt"), "original").unwrap();
+        std::fs::write(path("from.txt"), "renamed").unwrap();
+        std::fs::write(path("to.txt"), "replaced").unwrap();
+        std::fs::write(path("removed.txt"), [0xff, 0xfe, 0x00]).unwrap();
+
+        journal::init(false, Some(journal_dir.path())).unwrap();
+        write_to_file(&path("written.txt"), "changed").await.unwrap();
+        write_to_file(&path("created.txt"), "new").await.unwrap();
+        rename_file(&path("from.txt"), &path("to.txt")).await.unwrap();
+        remove_file(&path("removed.txt")).await.unwrap();
+        assert_eq!(std::fs::read_to_string(path("to.txt")).unwrap(), "renamed");
+
+        let args = Args { journal_dir: journal_dir.path().to_path_buf() };
+        run(args, &CommonArgs::default()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(path("written.txt")).unwrap(), "original");
+        assert!(!path("created.txt").exists());
+        assert_eq!(std::fs::read_to_string(path("from.txt")).unwrap(), "renamed");
+        assert_eq!(std::fs::read_to_string(path("to.txt")).unwrap(), "replaced");
+        assert_eq!(std::fs::read(path("removed.txt")).unwrap(), [0xff, 0xfe, 0x00]);
+
+        // A second undo would apply the renames again
+        let args = Args { journal_dir: journal_dir.path().to_path_buf() };
+        assert!(run(args, &CommonArgs::default()).await.is_err());
+    }
+}
+